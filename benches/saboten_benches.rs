@@ -2,6 +2,8 @@ use saboten::{
     biedgedgraph::*,
     cactusgraph,
     cactusgraph::{BridgeForest, CactusGraph, CactusTree},
+    projection::Projection,
+    snarls::Cactus,
 };
 
 use std::path::PathBuf;
@@ -9,13 +11,15 @@ use std::path::PathBuf;
 use gfa::{gfa::GFA, parser::GFAParser};
 
 use criterion::{
-    criterion_group, criterion_main, BenchmarkId, Criterion, Throughput,
+    criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion,
+    Throughput,
 };
 
 static GFAPATH: &str = "./test/gfas/";
 
 macro_rules! bench_graph_transforms {
     ($name:ident, $gfa:literal) => {
+        #[allow(dead_code)]
         fn $name(c: &mut Criterion) {
             let parser: GFAParser<usize, ()> = GFAParser::new();
             let mut path = PathBuf::from(GFAPATH);
@@ -24,7 +28,7 @@ macro_rules! bench_graph_transforms {
             c.bench_with_input(
                 BenchmarkId::new("graph transformations", $gfa),
                 &gfa,
-                |b, l| {
+                |b, _gfa| {
                     b.iter(|| {
                         let orig_graph = BiedgedGraph::from_gfa(&gfa);
 
@@ -45,6 +49,7 @@ macro_rules! bench_graph_transforms {
 
 macro_rules! bench_finding_snarls {
     ($name:ident, $gfa:literal) => {
+        #[allow(dead_code)]
         fn $name(c: &mut Criterion) {
             let parser: GFAParser<usize, ()> = GFAParser::new();
             let mut path = PathBuf::from(GFAPATH);
@@ -61,7 +66,7 @@ macro_rules! bench_finding_snarls {
             c.bench_with_input(
                 BenchmarkId::new("finding snarls", $gfa),
                 &gfa,
-                |b, l| {
+                |b, _gfa| {
                     b.iter(|| {
                         cactus_tree.find_chain_pairs();
 
@@ -84,14 +89,12 @@ macro_rules! bench_label_chain_edges {
             let orig_graph = BiedgedGraph::from_gfa(&gfa);
             let cactus_graph = CactusGraph::from_biedged_graph(&orig_graph);
             let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
-            let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
             let chain_pairs = cactus_tree.find_chain_pairs();
-            let bridge_pairs = bridge_forest.find_bridge_pairs();
 
             c.bench_with_input(
                 BenchmarkId::new("labeling chain edges", $gfa),
                 &gfa,
-                |b, l| {
+                |b, _gfa| {
                     b.iter(|| {
                         cactusgraph::chain_pair_ultrabubble_labels(
                             &cactus_tree,
@@ -120,7 +123,7 @@ macro_rules! bench_finding_ultrabubbles {
             c.bench_with_input(
                 BenchmarkId::new("finding ultrabubbles", $gfa),
                 &gfa,
-                |b, l| {
+                |b, _gfa| {
                     b.iter(|| {
                         cactusgraph::find_ultrabubbles(
                             &cactus_tree,
@@ -170,7 +173,7 @@ macro_rules! bench_build_net_graph {
                     |b, &chunk| {
                         b.iter(|| {
                             for &(x, y) in chunk.iter() {
-                                let net_graph =
+                                let _net_graph =
                                     cactus_tree.build_net_graph(x, y);
                             }
                         });
@@ -183,6 +186,81 @@ macro_rules! bench_build_net_graph {
     };
 }
 
+/// Time each stage of building the cactus graph separately, on random
+/// graphs with twice as many links as segments.
+fn pipeline_stages(c: &mut Criterion) {
+    for &segments in [1_000, 10_000, 50_000].iter() {
+        let graph = random_biedged(segments, segments * 2, 0x5ab0)
+            .set_graph_type::<Cactus>();
+        let projection = Projection::new_for_biedged_graph(&graph);
+
+        let mut contracted = graph.clone();
+        let mut contracted_proj = projection.copy_without_inverse();
+        CactusGraph::contract_all_gray_edges(
+            &mut contracted,
+            &mut contracted_proj,
+        );
+
+        let components =
+            CactusGraph::find_3_edge_connected_components_nodes(&contracted);
+
+        let mut group = c.benchmark_group("pipeline_stages");
+        group.throughput(Throughput::Elements(segments as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("contract_all_gray_edges", segments),
+            &graph,
+            |b, graph| {
+                b.iter_batched(
+                    || (graph.clone(), projection.copy_without_inverse()),
+                    |(mut graph, mut proj)| {
+                        CactusGraph::contract_all_gray_edges(
+                            &mut graph, &mut proj,
+                        );
+                        graph
+                    },
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("find_3_edge_connected_components", segments),
+            &contracted,
+            |b, contracted| {
+                b.iter(|| {
+                    CactusGraph::find_3_edge_connected_components(contracted)
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("merge_components", segments),
+            &contracted,
+            |b, contracted| {
+                b.iter_batched(
+                    || {
+                        (
+                            contracted.clone(),
+                            components.clone(),
+                            contracted_proj.copy_without_inverse(),
+                        )
+                    },
+                    |(mut graph, components, mut proj)| {
+                        CactusGraph::merge_components(
+                            &mut graph, components, &mut proj,
+                        );
+                        graph
+                    },
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+
+        group.finish();
+    }
+}
+
 bench_graph_transforms!(transform_a3015, "A-3105.gfa");
 bench_graph_transforms!(transform_covid, "relabeledSeqs.nopaths.gfa");
 
@@ -236,4 +314,9 @@ criterion_group!(
 
 // criterion_main!(transformations, snarls, labeling, ultrabubbles);
 // criterion_main!(labeling, ultrabubbles);
-criterion_main!(net_graphs);
+criterion_group!(
+    name = stages;
+    config = Criterion::default().sample_size(10);
+    targets = pipeline_stages);
+
+criterion_main!(net_graphs, stages);
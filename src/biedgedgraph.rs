@@ -1,13 +1,192 @@
 use petgraph::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::io::{self, BufRead};
 use std::ops::{Add, AddAssign, Sub, SubAssign};
 
-use gfa::gfa::{Orientation, GFA};
+use gfa::{
+    gfa::{Containment, Link, Orientation, Path, Segment, GFA},
+    optfields::OptFields,
+};
 
-use crate::projection::{id_to_black_edge, Projection};
+use crate::projection::{Projection, ProjectionLog};
 use crate::snarls::{Biedged, Node};
 
 use log::{debug, trace};
 
+/// The error produced when constructing a biedged graph from a GFA
+/// containing a segment ID too large for the IDs of the vertices at
+/// its ends to be represented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentIdOverflow(pub usize);
+
+impl std::fmt::Display for SegmentIdOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "segment ID {} is too large", self.0)
+    }
+}
+
+impl std::error::Error for SegmentIdOverflow {}
+
+/// The reasons `BiedgedGraph::try_from_gfa` can reject a GFA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BiedgedError {
+    /// The segment ID is too large; see `SegmentIdOverflow`.
+    SegmentIdOverflow(usize),
+    /// The link at index `link` in the GFA refers to a segment that
+    /// isn't in the GFA.
+    MissingSegment { link: usize, segment: usize },
+}
+
+impl std::fmt::Display for BiedgedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BiedgedError::SegmentIdOverflow(id) => {
+                SegmentIdOverflow(*id).fmt(f)
+            }
+            BiedgedError::MissingSegment { link, segment } => {
+                write!(f, "link {} refers to missing segment {}", link, segment)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BiedgedError {}
+
+impl From<SegmentIdOverflow> for BiedgedError {
+    fn from(SegmentIdOverflow(id): SegmentIdOverflow) -> Self {
+        BiedgedError::SegmentIdOverflow(id)
+    }
+}
+
+/// How the segment names in a GFA map to the segment IDs used in the
+/// biedged graph.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum NamingScheme {
+    /// The names are unsigned integers, used as the IDs directly.
+    #[default]
+    Integer,
+    /// The names are unsigned integers following a fixed prefix, as
+    /// with the `s1`, `s2`, ... names used by rGFA.
+    Prefixed(String),
+}
+
+impl NamingScheme {
+    /// The naming scheme used by rGFA, with an `s` before each ID.
+    pub fn rgfa() -> Self {
+        NamingScheme::Prefixed("s".to_string())
+    }
+
+    /// Returns the segment ID for the given name, or None if the name
+    /// doesn't follow the scheme.
+    pub fn parse_name(&self, name: &str) -> Option<usize> {
+        let id = match self {
+            NamingScheme::Integer => name,
+            NamingScheme::Prefixed(prefix) => {
+                name.strip_prefix(prefix.as_str())?
+            }
+        };
+        id.parse().ok()
+    }
+
+    /// Returns the segment name for the given ID, the inverse of
+    /// `parse_name`.
+    pub fn segment_name(&self, id: usize) -> String {
+        match self {
+            NamingScheme::Integer => id.to_string(),
+            NamingScheme::Prefixed(prefix) => format!("{}{}", prefix, id),
+        }
+    }
+
+    fn parse_name_bytes(&self, name: &[u8]) -> Option<usize> {
+        self.parse_name(std::str::from_utf8(name).ok()?)
+    }
+
+    /// Convert a GFA with bytestring segment names to one with the
+    /// segment IDs given by the scheme, like
+    /// `NameMap::gfa_bytestring_to_usize`, so it can be passed to
+    /// `BiedgedGraph::from_gfa`. Returns None if any segment name,
+    /// including those in links, containments and paths, doesn't
+    /// follow the scheme.
+    pub fn gfa_to_usize<T: OptFields>(
+        &self,
+        gfa: &GFA<Vec<u8>, T>,
+    ) -> Option<GFA<usize, T>> {
+        let segments = gfa
+            .segments
+            .iter()
+            .map(|seg| {
+                Some(Segment {
+                    name: self.parse_name_bytes(&seg.name)?,
+                    sequence: seg.sequence.clone(),
+                    optional: seg.optional.clone(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let links = gfa
+            .links
+            .iter()
+            .map(|link| {
+                Some(Link {
+                    from_segment: self.parse_name_bytes(&link.from_segment)?,
+                    from_orient: link.from_orient,
+                    to_segment: self.parse_name_bytes(&link.to_segment)?,
+                    to_orient: link.to_orient,
+                    overlap: link.overlap.clone(),
+                    optional: link.optional.clone(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let containments = gfa
+            .containments
+            .iter()
+            .map(|cont| {
+                Some(Containment {
+                    container_name: self
+                        .parse_name_bytes(&cont.container_name)?,
+                    container_orient: cont.container_orient,
+                    contained_name: self
+                        .parse_name_bytes(&cont.contained_name)?,
+                    contained_orient: cont.contained_orient,
+                    pos: cont.pos,
+                    overlap: cont.overlap.clone(),
+                    optional: cont.optional.clone(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let paths = gfa
+            .paths
+            .iter()
+            .map(|path| {
+                let steps = path
+                    .iter()
+                    .map(|(seg, orient)| {
+                        let id = self.parse_name_bytes(seg)?;
+                        Some(format!("{}{}", id, orient))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                Some(Path::new(
+                    path.path_name.clone(),
+                    steps.join(",").into_bytes(),
+                    path.overlaps.clone(),
+                    path.optional.clone(),
+                ))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(GFA {
+            header: gfa.header.clone(),
+            segments,
+            links,
+            containments,
+            paths,
+        })
+    }
+}
+
 /// To make a petgraph Graph(Map) into a multigraph, we track the
 /// number of black and gray edges between two nodes by using this
 /// struct as the edge weight type.
@@ -43,6 +222,27 @@ impl BiedgedWeight {
     pub fn gray(gray: usize) -> Self {
         BiedgedWeight { black: 0, gray }
     }
+
+    /// The total number of edges, of both colors, this weight
+    /// represents.
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.black + self.gray
+    }
+
+    /// True if the weight has at least one black edge and no gray
+    /// edges.
+    #[inline]
+    pub fn black_only(&self) -> bool {
+        self.black > 0 && self.gray == 0
+    }
+
+    /// True if the weight has at least one gray edge and no black
+    /// edges.
+    #[inline]
+    pub fn gray_only(&self) -> bool {
+        self.gray > 0 && self.black == 0
+    }
 }
 
 /// Adding two BiedgedWeights adds their corresponding edges, which
@@ -87,10 +287,164 @@ impl SubAssign for BiedgedWeight {
     }
 }
 
+/// The changes made to a graph by a single edge contraction, as
+/// returned by `BiedgedGraph::contract_edge_logged`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractionRecord {
+    /// The vertex the contracted edge was merged into.
+    pub kept: Node,
+    /// The vertex that was removed from the graph.
+    pub removed: Node,
+    /// The weight of the contracted edge.
+    pub weight: BiedgedWeight,
+    /// The weight of the kept vertex's self-loop before the
+    /// contraction, if it had one.
+    pub kept_self_weight: Option<BiedgedWeight>,
+    /// The weight of the removed vertex's self-loop, if it had one.
+    pub removed_self_weight: Option<BiedgedWeight>,
+    /// The other edges of the removed vertex, as pairs of neighbor
+    /// and edge weight, which were moved to the kept vertex.
+    pub rewired: Vec<(Node, BiedgedWeight)>,
+    /// The changes the contraction made to the projection.
+    pub projection_log: ProjectionLog,
+}
+
+/// A sequence of edge contractions on a graph and its projection that
+/// can be undone as a whole. Created by `BiedgedGraph::transaction`.
+///
+/// The contractions are kept if the transaction is committed, and
+/// undone if it's rolled back, or dropped without being committed.
+pub struct Transaction<'a, G: Copy> {
+    graph: &'a mut BiedgedGraph<G>,
+    projection: &'a mut Projection,
+    records: Vec<ContractionRecord>,
+    // The changes to the projection made by contractions that found
+    // no edge, each with the number of records made before it
+    stray_logs: Vec<(usize, ProjectionLog)>,
+}
+
+impl<'a, G: Copy> Transaction<'a, G> {
+    /// Contract an edge as `BiedgedGraph::contract_edge` does,
+    /// recording the change so it can be rolled back.
+    pub fn contract_edge(&mut self, left: Node, right: Node) -> Option<Node> {
+        match self
+            .graph
+            .contract_edge_or_log(left, right, self.projection)
+        {
+            Ok(record) => {
+                let kept = record.kept;
+                self.records.push(record);
+                Some(kept)
+            }
+            Err(log) => {
+                self.stray_logs.push((self.records.len(), log));
+                None
+            }
+        }
+    }
+
+    #[inline]
+    pub fn graph(&self) -> &BiedgedGraph<G> {
+        self.graph
+    }
+
+    #[inline]
+    pub fn projection(&self) -> &Projection {
+        self.projection
+    }
+
+    /// The contractions made so far in the transaction.
+    #[inline]
+    pub fn records(&self) -> &[ContractionRecord] {
+        &self.records
+    }
+
+    /// Keep the contractions made in the transaction.
+    pub fn commit(mut self) {
+        self.records.clear();
+        self.stray_logs.clear();
+    }
+
+    /// Undo the contractions made in the transaction, restoring the
+    /// graph and projection to their state from before it started.
+    pub fn rollback(mut self) {
+        self.undo_all();
+    }
+
+    fn undo_all(&mut self) {
+        while let Some(mut record) = self.records.pop() {
+            self.undo_stray_logs(self.records.len() + 1);
+
+            let log = std::mem::take(&mut record.projection_log);
+            self.graph.undo_contraction(record);
+            self.projection.undo(log);
+        }
+
+        self.undo_stray_logs(0);
+    }
+
+    /// Undo the stray projection changes made after the first
+    /// `records` records.
+    fn undo_stray_logs(&mut self, records: usize) {
+        while self.stray_logs.last().is_some_and(|(ix, _)| *ix >= records) {
+            let (_, log) = self.stray_logs.pop().unwrap();
+            self.projection.undo(log);
+        }
+    }
+}
+
+impl<'a, G: Copy> Drop for Transaction<'a, G> {
+    fn drop(&mut self) {
+        self.undo_all();
+    }
+}
+
+/// Metadata attached to the black edges of a biedged graph built from
+/// a GFA, e.g. the lengths of the segments. Constructed by
+/// `BiedgedGraph::from_gfa_with`.
+#[derive(Debug, Clone)]
+pub struct BlackEdgeMeta<M> {
+    meta: FxHashMap<Node, M>,
+}
+
+impl<M> Default for BlackEdgeMeta<M> {
+    fn default() -> Self {
+        Self {
+            meta: Default::default(),
+        }
+    }
+}
+
+impl<M> BlackEdgeMeta<M> {
+    /// Returns the metadata of the black edge the given vertex is
+    /// incident to, if any.
+    #[inline]
+    pub fn edge_meta(&self, node: Node) -> Option<&M> {
+        self.meta.get(&node.left())
+    }
+
+    /// Set the metadata of the black edge incident to `node`,
+    /// returning the previous value, if any.
+    #[inline]
+    pub fn insert(&mut self, node: Node, meta: M) -> Option<M> {
+        self.meta.insert(node.left(), meta)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.meta.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.meta.is_empty()
+    }
+}
+
 /// A biedged graph is a graph with two types of edges: black edges
 /// and gray edges, such that each vertex is incident with at most one
 /// black edge.
-
+///
 /// To simplify differentiating between net vertices and chain
 /// vertices in the cactus graph, all chain vertices have an index
 /// higher than the original vertices. This also makes it easier to
@@ -114,6 +468,48 @@ impl<G> std::default::Default for BiedgedGraph<G> {
     }
 }
 
+/// Two biedged graphs are equal if they have the same vertices, and
+/// the same number of black and gray edges between each pair of
+/// vertices, regardless of the order they were added in.
+impl<G> PartialEq for BiedgedGraph<G> {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical_nodes() == other.canonical_nodes()
+            && self.canonical_edges() == other.canonical_edges()
+    }
+}
+
+impl<G> Eq for BiedgedGraph<G> {}
+
+impl<G> BiedgedGraph<G> {
+    fn canonical_nodes(&self) -> Vec<Node> {
+        let mut nodes = self.graph.nodes().collect::<Vec<_>>();
+        nodes.sort_unstable();
+        nodes
+    }
+
+    fn canonical_edges(&self) -> Vec<(Node, Node, usize, usize)> {
+        let mut edges = self
+            .graph
+            .all_edges()
+            .map(|(a, b, w)| (a.min(b), a.max(b), w.black, w.gray))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+    }
+
+    /// Hashes the vertices and edges of the graph, such that graphs
+    /// that are equal have the same hash, independent of the order
+    /// their vertices and edges were added in.
+    pub fn canonical_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = rustc_hash::FxHasher::default();
+        self.canonical_nodes().hash(&mut hasher);
+        self.canonical_edges().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
 impl<G> BiedgedGraph<G>
 where
     G: Copy,
@@ -277,6 +673,15 @@ where
         n <= self.max_net_vertex
     }
 
+    /// Returns an iterator over the net vertices in the graph, i.e.
+    /// every vertex that isn't a chain vertex. In a cactus graph,
+    /// these are the vertices that represent the merged components.
+    #[inline]
+    pub fn net_vertices(&self) -> impl Iterator<Item = Node> + '_ {
+        let max_net_vertex = self.max_net_vertex;
+        self.graph.nodes().filter(move |&n| n <= max_net_vertex)
+    }
+
     /// Convenience method for getting the projection of a node,
     /// taking the possibility of chain vertices into account
     #[inline]
@@ -288,6 +693,28 @@ where
         }
     }
 
+    /// Construct a biedged graph directly from lists of black and
+    /// gray edges between biedged vertex IDs, each edge with a count
+    /// of one. Repeated edges are added to the edge weights, and the
+    /// vertices are added implicitly. All vertices are treated as
+    /// net vertices.
+    pub fn from_edges(black: &[(u64, u64)], gray: &[(u64, u64)]) -> Self {
+        let mut graph: BiedgedGraph<G> = Default::default();
+
+        let black_iter = black.iter().map(|e| (e, BiedgedWeight::black(1)));
+        let gray_iter = gray.iter().map(|e| (e, BiedgedWeight::gray(1)));
+
+        for (&(a, b), w) in black_iter.chain(gray_iter) {
+            graph.add_edge(Node::from(a), Node::from(b), w);
+        }
+
+        let max_node = graph.graph.nodes().max().unwrap_or_default();
+        graph.max_net_vertex = max_node;
+        graph.max_chain_vertex = max_node;
+
+        graph
+    }
+
     pub fn from_directed_edges<I>(i: I) -> Option<Self>
     where
         I: IntoIterator<Item = (u64, u64)>,
@@ -307,8 +734,8 @@ where
     {
         use Orientation::*;
 
-        let mut min_node_id = std::u64::MAX;
-        let mut max_node_id = std::u64::MIN;
+        let mut min_node_id = u64::MAX;
+        let mut max_node_id = u64::MIN;
 
         let mut graph: UnGraphMap<Node, BiedgedWeight> = UnGraphMap::new();
 
@@ -355,8 +782,66 @@ where
         })
     }
 
-    /// Construct a biedged graph from a GFA.
-    pub fn from_gfa(gfa: &GFA<usize, ()>) -> Self {
+    /// Construct a biedged graph from a GFA. A GFA with other segment
+    /// names can be converted first using either a `NameMap` or the
+    /// `NamingScheme` the names follow, with `NamingScheme::gfa_to_usize`;
+    /// `projected_node_name` then names the vertices the same way.
+    ///
+    /// Panics if the GFA contains a segment ID too large to be
+    /// represented in the biedged graph; see `try_from_gfa`.
+    pub fn from_gfa<T: OptFields>(gfa: &GFA<usize, T>) -> Self {
+        match Self::build_from_gfa(gfa, true) {
+            Ok(graph) => graph,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Construct a biedged graph from a GFA, ignoring the orientations
+    /// of the links. Each link is first put on a canonical strand:
+    /// the one where both segments are forward, if there is one, and
+    /// otherwise the one starting at the segment with the smaller ID.
+    /// It then connects the right-hand side of its `from` segment to
+    /// the left-hand side of its `to` segment, as if both segments
+    /// were forward, so both ways of writing a link give the same gray
+    /// edge.
+    ///
+    /// Panics if the GFA contains a segment ID too large to be
+    /// represented in the biedged graph; see `try_from_gfa`.
+    pub fn from_gfa_undirected<T: OptFields>(gfa: &GFA<usize, T>) -> Self {
+        match Self::build_from_gfa(gfa, false) {
+            Ok(graph) => graph,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Construct a biedged graph from a GFA, failing instead of
+    /// panicking if the GFA contains a segment ID too large to be
+    /// represented in the biedged graph, or a link to a segment that
+    /// isn't in the GFA.
+    pub fn try_from_gfa<T: OptFields>(
+        gfa: &GFA<usize, T>,
+    ) -> Result<Self, BiedgedError> {
+        let segments: FxHashSet<usize> =
+            gfa.segments.iter().map(|s| s.name).collect();
+
+        for (ix, link) in gfa.links.iter().enumerate() {
+            for &segment in [link.from_segment, link.to_segment].iter() {
+                if !segments.contains(&segment) {
+                    return Err(BiedgedError::MissingSegment {
+                        link: ix,
+                        segment,
+                    });
+                }
+            }
+        }
+
+        Ok(Self::build_from_gfa(gfa, true)?)
+    }
+
+    fn build_from_gfa<T: OptFields>(
+        gfa: &GFA<usize, T>,
+        oriented: bool,
+    ) -> Result<Self, SegmentIdOverflow> {
         debug!(
             "building BiedgedGraph from GFA with {} nodes, {} edges",
             gfa.segments.len(),
@@ -370,45 +855,187 @@ where
             UnGraphMap::with_capacity(segs_len * 2, segs_len + links_len);
 
         let mut max_seg_id = 0;
-        let mut min_seg_id = std::usize::MAX;
+        let mut min_seg_id = usize::MAX;
         let mut max_node_id = 0;
 
         for segment in gfa.segments.iter() {
-            let (left, right) = Node::from_gfa_id(segment.name as u64);
+            Self::add_gfa_segment(&mut be_graph, segment.name)?;
 
             max_node_id = max_node_id.max(segment.name);
             max_seg_id = segment.name.max(max_seg_id);
             min_seg_id = segment.name.min(min_seg_id);
+        }
+
+        for link in gfa.links.iter() {
+            let (from, to) = if oriented {
+                (
+                    (link.from_segment, link.from_orient),
+                    (link.to_segment, link.to_orient),
+                )
+            } else {
+                // a link and its reverse complement, e.g. 1+ -> 2+ and
+                // 2- -> 1-, are the same link, so they must end up on
+                // the same strand
+                let (from, to) = (link.from_segment, link.to_segment);
+                let (from, to) = match (link.from_orient, link.to_orient) {
+                    (Orientation::Forward, Orientation::Forward) => (from, to),
+                    (Orientation::Backward, Orientation::Backward) => {
+                        (to, from)
+                    }
+                    _ => (from.min(to), from.max(to)),
+                };
+                ((from, Orientation::Forward), (to, Orientation::Forward))
+            };
 
-            be_graph.add_node(left);
-            be_graph.add_node(right);
-            be_graph.add_edge(left, right, BiedgedWeight::black(1));
+            Self::add_gfa_link(&mut be_graph, from, to)?;
         }
 
-        use Orientation::*;
+        Self::from_gfa_graph(be_graph, max_node_id)
+    }
 
-        for link in gfa.links.iter() {
-            let from_o = link.from_orient;
-            let to_o = link.to_orient;
+    /// Construct a biedged graph by reading a GFA line by line, only
+    /// keeping the segment names and the links. The segment names
+    /// must be unsigned integers, as in `GFA<usize, _>`; lines other
+    /// than segments and links are ignored. Produces the same graph
+    /// as `from_gfa`, without first parsing the entire GFA.
+    pub fn from_gfa_reader<R: BufRead>(reader: R) -> io::Result<Self> {
+        Self::from_gfa_reader_named(reader, &NamingScheme::Integer)
+    }
 
-            let from = Node::from_gfa_id(link.from_segment as u64);
-            let to = Node::from_gfa_id(link.to_segment as u64);
+    /// Like `from_gfa_reader`, but with the segment names parsed
+    /// according to `naming`, e.g. `NamingScheme::rgfa()` for a GFA
+    /// with rGFA-style names.
+    pub fn from_gfa_reader_named<R: BufRead>(
+        reader: R,
+        naming: &NamingScheme,
+    ) -> io::Result<Self> {
+        fn invalid<E: std::fmt::Display>(line: usize, err: E) -> io::Error {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("GFA line {}: {}", line, err),
+            )
+        }
 
-            let (left, right) = match (from_o, to_o) {
-                (Forward, Forward) => (from.1, to.0),
-                (Backward, Backward) => (to.1, from.0),
-                (Forward, Backward) => (from.1, to.1),
-                (Backward, Forward) => (from.0, to.0),
-            };
+        let parse_name = |line: usize, field: Option<&str>| {
+            let field = field.ok_or_else(|| invalid(line, "missing field"))?;
+            naming.parse_name(field).ok_or_else(|| {
+                invalid(line, format!("invalid segment name {}", field))
+            })
+        };
 
-            if let Some(w) = be_graph.edge_weight_mut(left, right) {
-                *w += BiedgedWeight::gray(1);
-            } else {
-                be_graph.add_edge(left, right, BiedgedWeight::gray(1));
+        fn parse_orient(
+            line: usize,
+            field: Option<&str>,
+        ) -> io::Result<Orientation> {
+            match field {
+                Some("+") => Ok(Orientation::Forward),
+                Some("-") => Ok(Orientation::Backward),
+                _ => Err(invalid(line, "invalid orientation")),
+            }
+        }
+
+        let mut be_graph: UnGraphMap<Node, BiedgedWeight> = UnGraphMap::new();
+
+        let mut max_node_id = 0;
+
+        for (ix, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_num = ix + 1;
+            let mut fields = line.split('\t');
+
+            match fields.next() {
+                Some("S") => {
+                    let name = parse_name(line_num, fields.next())?;
+                    Self::add_gfa_segment(&mut be_graph, name)
+                        .map_err(|e| invalid(line_num, e))?;
+                    max_node_id = max_node_id.max(name);
+                }
+                Some("L") => {
+                    let from = parse_name(line_num, fields.next())?;
+                    let from_o = parse_orient(line_num, fields.next())?;
+                    let to = parse_name(line_num, fields.next())?;
+                    let to_o = parse_orient(line_num, fields.next())?;
+                    Self::add_gfa_link(
+                        &mut be_graph,
+                        (from, from_o),
+                        (to, to_o),
+                    )
+                    .map_err(|e| invalid(line_num, e))?;
+                }
+                _ => (),
             }
         }
 
-        let max_net_vertex = ((max_node_id + 1) * 2) as u64;
+        Self::from_gfa_graph(be_graph, max_node_id)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn segment_nodes(name: usize) -> Result<(Node, Node), SegmentIdOverflow> {
+        Node::try_from_gfa_id(name as u64).ok_or(SegmentIdOverflow(name))
+    }
+
+    fn add_gfa_segment(
+        graph: &mut UnGraphMap<Node, BiedgedWeight>,
+        name: usize,
+    ) -> Result<(), SegmentIdOverflow> {
+        let (left, right) = Self::segment_nodes(name)?;
+
+        graph.add_node(left);
+        graph.add_node(right);
+        graph.add_edge(left, right, BiedgedWeight::black(1));
+
+        Ok(())
+    }
+
+    /// Given the black edges of two segments, find the vertices the
+    /// gray edge of a link between them connects.
+    fn link_endpoints(
+        from: (Node, Node),
+        from_rev: bool,
+        to: (Node, Node),
+        to_rev: bool,
+    ) -> (Node, Node) {
+        match (from_rev, to_rev) {
+            (false, false) => (from.1, to.0),
+            (true, true) => (to.1, from.0),
+            (false, true) => (from.1, to.1),
+            (true, false) => (from.0, to.0),
+        }
+    }
+
+    fn add_gfa_link(
+        graph: &mut UnGraphMap<Node, BiedgedWeight>,
+        (from, from_o): (usize, Orientation),
+        (to, to_o): (usize, Orientation),
+    ) -> Result<(), SegmentIdOverflow> {
+        let from = Self::segment_nodes(from)?;
+        let to = Self::segment_nodes(to)?;
+
+        let (left, right) = Self::link_endpoints(
+            from,
+            from_o == Orientation::Backward,
+            to,
+            to_o == Orientation::Backward,
+        );
+
+        if let Some(w) = graph.edge_weight_mut(left, right) {
+            *w += BiedgedWeight::gray(1);
+        } else {
+            graph.add_edge(left, right, BiedgedWeight::gray(1));
+        }
+
+        Ok(())
+    }
+
+    fn from_gfa_graph(
+        be_graph: UnGraphMap<Node, BiedgedWeight>,
+        max_node_id: usize,
+    ) -> Result<Self, SegmentIdOverflow> {
+        let (_, right) = Self::segment_nodes(max_node_id)?;
+        let max_net_vertex = right
+            .id
+            .checked_add(1)
+            .ok_or(SegmentIdOverflow(max_node_id))?;
         let max_chain_vertex = max_net_vertex;
 
         let (node_cap, edge_cap) = be_graph.capacity();
@@ -416,12 +1043,35 @@ where
         debug!("BiedgedGraph with {} nodes, {} edges, capacity: {} nodes, {} edges",
                be_graph.node_count(), be_graph.edge_count(), node_cap, edge_cap);
 
-        BiedgedGraph {
+        Ok(BiedgedGraph {
             graph: be_graph,
             max_net_vertex: max_net_vertex.into(),
             max_chain_vertex: max_chain_vertex.into(),
             _graph: std::marker::PhantomData,
+        })
+    }
+
+    /// Construct a biedged graph from a GFA as `from_gfa` does, and
+    /// attach the result of applying `f` to each segment to the
+    /// black edge representing the segment.
+    pub fn from_gfa_with<T, M, F>(
+        gfa: &GFA<usize, T>,
+        f: F,
+    ) -> (Self, BlackEdgeMeta<M>)
+    where
+        T: OptFields,
+        F: Fn(&Segment<usize, T>) -> M,
+    {
+        let graph = Self::from_gfa(gfa);
+
+        let mut meta = BlackEdgeMeta::default();
+
+        for segment in gfa.segments.iter() {
+            let (left, _right) = Node::from_gfa_id(segment.name as u64);
+            meta.insert(left, f(segment));
         }
+
+        (graph, meta)
     }
 
     /// Add the node with the given id to the graph
@@ -430,6 +1080,51 @@ where
         self.graph.add_node(Node::from(id))
     }
 
+    /// Returns an iterator over the IDs of the vertices in the graph.
+    #[inline]
+    pub fn node_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.graph.nodes().map(|n| n.id)
+    }
+
+    /// Returns an iterator over the edges in the graph, as the IDs of
+    /// the two vertices and the weight of the edge. Each edge is
+    /// only included once.
+    #[inline]
+    pub fn edges(
+        &self,
+    ) -> impl Iterator<Item = (u64, u64, BiedgedWeight)> + '_ {
+        self.graph.all_edges().map(|(a, b, w)| (a.id, b.id, *w))
+    }
+
+    #[inline]
+    pub fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    /// Returns true if the graph has no vertices, such as when it's
+    /// built from a GFA without segments.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.graph.node_count() == 0
+    }
+
+    /// Returns the number of edges in the graph, not counting edge
+    /// multiplicities.
+    #[inline]
+    pub fn edge_count(&self) -> usize {
+        self.graph.edge_count()
+    }
+
+    #[inline]
+    pub fn contains_node(&self, n: Node) -> bool {
+        self.graph.contains_node(n)
+    }
+
+    #[inline]
+    pub fn edge_weight(&self, a: Node, b: Node) -> Option<BiedgedWeight> {
+        self.graph.edge_weight(a, b).copied()
+    }
+
     /// Add an edge with the provided edge weight. If a corresponding
     /// edge already exists in the graph, the edge weights are added.
     #[inline]
@@ -441,6 +1136,28 @@ where
         }
     }
 
+    /// Add a gray edge for a GFA link between the segments with IDs
+    /// `from_gfa` and `to_gfa`, connecting the sides of the segments
+    /// given by their orientations in the link, e.g. a `+/-` link
+    /// connects the right-hand sides of both. Returns the vertices
+    /// connected by the gray edge.
+    pub fn add_link(
+        &mut self,
+        from_gfa: u64,
+        from_rev: bool,
+        to_gfa: u64,
+        to_rev: bool,
+    ) -> (Node, Node) {
+        let (left, right) = Self::link_endpoints(
+            Node::from_gfa_id(from_gfa),
+            from_rev,
+            Node::from_gfa_id(to_gfa),
+            to_rev,
+        );
+        self.add_edge(left, right, BiedgedWeight::gray(1));
+        (left, right)
+    }
+
     /// Returns an iterator over the gray edges in the graph, where
     /// the first two elements in the tuple are the `from` and `to`
     /// nodes, and the third is the weight containing the number of
@@ -473,6 +1190,43 @@ where
         self.graph.all_edges().filter(|(_, _, w)| w.black > 0)
     }
 
+    /// Returns a copy of the graph containing all of its vertices, but
+    /// only the gray edges.
+    pub fn gray_subgraph(&self) -> Self {
+        self.filter_weights(|w| BiedgedWeight::gray(w.gray))
+    }
+
+    /// Returns a copy of the graph containing all of its vertices, but
+    /// only the black edges.
+    pub fn black_subgraph(&self) -> Self {
+        self.filter_weights(|w| BiedgedWeight::black(w.black))
+    }
+
+    fn filter_weights<F>(&self, f: F) -> Self
+    where
+        F: Fn(&BiedgedWeight) -> BiedgedWeight,
+    {
+        let mut graph: UnGraphMap<Node, BiedgedWeight> = UnGraphMap::new();
+
+        for node in self.graph.nodes() {
+            graph.add_node(node);
+        }
+
+        for (a, b, w) in self.graph.all_edges() {
+            let w = f(w);
+            if w.total() > 0 {
+                graph.add_edge(a, b, w);
+            }
+        }
+
+        BiedgedGraph {
+            graph,
+            max_net_vertex: self.max_net_vertex,
+            max_chain_vertex: self.max_chain_vertex,
+            _graph: std::marker::PhantomData,
+        }
+    }
+
     /// Produces the sum of the gray edges in the graph, counted using
     /// the edge weights.
     #[inline]
@@ -487,29 +1241,322 @@ where
         self.black_edges().map(|(_, _, w)| w.black).sum()
     }
 
-    /// Remove a single black edge between two nodes, if any exists.
-    /// If the nodes share more than one black edge, their
-    /// corresponding edge weight is decremented, but they will still
-    /// have an edge in the graph.
+    /// Sums the given count over the edges incident to `n`, with
+    /// self-loops counted twice, as they're incident at both ends.
     #[inline]
-    pub fn remove_one_black_edge(&mut self, a: Node, b: Node) -> Option<usize> {
-        use std::cmp::Ordering;
+    fn weighted_degree<F>(&self, n: Node, count: F) -> usize
+    where
+        F: Fn(&BiedgedWeight) -> usize,
+    {
+        self.graph
+            .edges(n)
+            .map(|(a, b, w)| if a == b { 2 * count(w) } else { count(w) })
+            .sum()
+    }
 
-        let weight = self.graph.edge_weight_mut(a, b)?;
+    /// The number of black edges incident to `n`, counted using the
+    /// edge weights.
+    #[inline]
+    pub fn black_degree(&self, n: Node) -> usize {
+        self.weighted_degree(n, |w| w.black)
+    }
 
-        match weight.black.cmp(&1) {
-            Ordering::Greater => {
-                weight.black -= 1;
-                Some(weight.black)
-            }
-            Ordering::Equal => {
-                self.graph.remove_edge(a, b);
-                Some(0)
+    /// The number of gray edges incident to `n`, counted using the
+    /// edge weights.
+    #[inline]
+    pub fn gray_degree(&self, n: Node) -> usize {
+        self.weighted_degree(n, |w| w.gray)
+    }
+
+    /// The number of edges of either color incident to `n`, counted
+    /// using the edge weights.
+    #[inline]
+    pub fn total_degree(&self, n: Node) -> usize {
+        self.weighted_degree(n, BiedgedWeight::total)
+    }
+
+    /// Returns true if the graph has no gray edges left, meaning that
+    /// it can be used to find 3-edge-connected components and the
+    /// rest of the cactus graph construction.
+    #[inline]
+    pub fn is_cactus_ready(&self) -> bool {
+        self.gray_edges().next().is_none()
+    }
+
+    /// Returns true if `node` is in the graph and has a black edge to
+    /// the other side of its segment.
+    #[inline]
+    pub fn is_black_endpoint(&self, node: Node) -> bool {
+        self.graph
+            .edge_weight(node, node.opposite())
+            .is_some_and(|w| w.black > 0)
+    }
+
+    /// Returns the tips of the graph, in ascending order: the
+    /// vertices at dead ends, whose only edge is the black edge of
+    /// their segment, with no gray edges connecting them to the rest
+    /// of the graph.
+    pub fn tips(&self) -> Vec<Node> {
+        let mut tips = self
+            .graph
+            .nodes()
+            .filter(|&n| {
+                self.is_black_endpoint(n)
+                    && self.black_degree(n) == 1
+                    && self.gray_degree(n) == 0
+            })
+            .collect::<Vec<_>>();
+        tips.sort_unstable();
+        tips
+    }
+
+    /// Checks that the graph is a well-formed biedged graph, as built
+    /// from a GFA: each vertex has a single black edge, to the other
+    /// side of its segment, and no vertex ID is above
+    /// `max_net_vertex`. Returns the first vertex, in ID order, that
+    /// breaks this. Graphs whose edges have been contracted are not
+    /// expected to pass.
+    pub fn validate(&self) -> Result<(), Node> {
+        let mut nodes = self.graph.nodes().collect::<Vec<_>>();
+        nodes.sort_unstable();
+
+        for node in nodes {
+            let black = |other: Node| {
+                self.graph.edge_weight(node, other).map_or(0, |w| w.black)
+            };
+
+            let stray_black = self
+                .graph
+                .neighbors(node)
+                .any(|other| other != node.opposite() && black(other) > 0);
+
+            if node > self.max_net_vertex
+                || black(node.opposite()) != 1
+                || stray_black
+            {
+                return Err(node);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the connected components of the graph, with each
+    /// component's vertices sorted, and the components sorted by
+    /// their smallest vertex.
+    pub fn connected_components(&self) -> Vec<Vec<Node>> {
+        let mut visited: FxHashSet<Node> = FxHashSet::default();
+        let mut stack: Vec<Node> = Vec::new();
+
+        let mut components = Vec::new();
+
+        let mut nodes = self.graph.nodes().collect::<Vec<_>>();
+        nodes.sort_unstable();
+
+        for node in nodes {
+            if visited.contains(&node) {
+                continue;
+            }
+
+            let mut component = Vec::new();
+
+            visited.insert(node);
+            stack.push(node);
+
+            while let Some(current) = stack.pop() {
+                component.push(current);
+                for other in self.graph.neighbors(current) {
+                    if !visited.contains(&other) {
+                        visited.insert(other);
+                        stack.push(other);
+                    }
+                }
+            }
+
+            component.sort_unstable();
+            components.push(component);
+        }
+
+        components
+    }
+
+    /// Returns the black edges whose removal would disconnect the
+    /// graph, found using a depth-first search that tracks the lowest
+    /// discovery time reachable from each vertex. Parallel edges, of
+    /// either color, are never bridges. The edges are returned with
+    /// the smaller vertex first, in sorted order.
+    pub fn bridges(&self) -> Vec<(Node, Node)> {
+        self.bridges_and_cut_vertices().0
+    }
+
+    /// Find the cut vertices of the graph, i.e. the vertices whose
+    /// removal would increase the number of connected components.
+    /// In a cactus graph, these are the vertices shared by more than
+    /// one cycle or bridge.
+    pub fn cut_vertices(&self) -> FxHashSet<Node> {
+        self.bridges_and_cut_vertices().1
+    }
+
+    /// The low-link depth-first search behind `bridges` and
+    /// `cut_vertices`, which finds both in a single traversal. A
+    /// child whose lowest reachable discovery time is later than its
+    /// parent's is on the far side of a bridge, and one whose lowest
+    /// time is no earlier than its parent's makes the parent a cut
+    /// vertex, unless the parent is a DFS root, which is a cut vertex
+    /// if it has more than one child.
+    fn bridges_and_cut_vertices(&self) -> (Vec<(Node, Node)>, FxHashSet<Node>) {
+        let mut disc: FxHashMap<Node, usize> = FxHashMap::default();
+        let mut low: FxHashMap<Node, usize> = FxHashMap::default();
+
+        let mut bridges = Vec::new();
+        let mut cut_vertices = FxHashSet::default();
+
+        let mut nodes = self.graph.nodes().collect::<Vec<_>>();
+        nodes.sort_unstable();
+
+        // Each stack frame holds a vertex, its DFS parent, and the
+        // index of the next neighbor to visit
+        let mut stack: Vec<(Node, Option<Node>, Vec<Node>, usize)> = Vec::new();
+
+        let mut time = 0;
+
+        for root in nodes {
+            if disc.contains_key(&root) {
+                continue;
+            }
+
+            disc.insert(root, time);
+            low.insert(root, time);
+            time += 1;
+
+            let mut root_children = 0;
+
+            let neighbors = self.graph.neighbors(root).collect();
+            stack.push((root, None, neighbors, 0));
+
+            while let Some((node, parent, neighbors, ix)) = stack.last_mut() {
+                let node = *node;
+                let parent = *parent;
+
+                if let Some(&other) = neighbors.get(*ix) {
+                    *ix += 1;
+
+                    if other == node || Some(other) == parent {
+                        continue;
+                    }
+
+                    if let Some(&other_disc) = disc.get(&other) {
+                        let node_low = low.get_mut(&node).unwrap();
+                        *node_low = (*node_low).min(other_disc);
+                    } else {
+                        disc.insert(other, time);
+                        low.insert(other, time);
+                        time += 1;
+
+                        if node == root {
+                            root_children += 1;
+                        }
+
+                        let neighbors = self.graph.neighbors(other).collect();
+                        stack.push((other, Some(node), neighbors, 0));
+                    }
+                } else {
+                    stack.pop();
+
+                    if let Some(parent) = parent {
+                        let node_low = low[&node];
+                        let parent_low = low.get_mut(&parent).unwrap();
+                        *parent_low = (*parent_low).min(node_low);
+
+                        let parent_disc = disc[&parent];
+
+                        let w = self.graph.edge_weight(parent, node).unwrap();
+
+                        if node_low > parent_disc && w.black == 1 && w.gray == 0
+                        {
+                            bridges.push((parent.min(node), parent.max(node)));
+                        }
+
+                        if parent != root && node_low >= parent_disc {
+                            cut_vertices.insert(parent);
+                        }
+                    }
+                }
+            }
+
+            if root_children > 1 {
+                cut_vertices.insert(root);
+            }
+        }
+
+        bridges.sort_unstable();
+        (bridges, cut_vertices)
+    }
+
+    /// Remove a single black edge between two nodes, if any exists.
+    /// If the nodes share more than one black edge, their
+    /// corresponding edge weight is decremented, but they will still
+    /// have an edge in the graph.
+    #[inline]
+    pub fn remove_one_black_edge(&mut self, a: Node, b: Node) -> Option<usize> {
+        use std::cmp::Ordering;
+
+        let weight = self.graph.edge_weight_mut(a, b)?;
+
+        match weight.black.cmp(&1) {
+            Ordering::Greater => {
+                weight.black -= 1;
+                Some(weight.black)
+            }
+            Ordering::Equal => {
+                self.graph.remove_edge(a, b);
+                Some(0)
             }
             Ordering::Less => None,
         }
     }
 
+    /// Remove every black and gray edge that starts and ends at the
+    /// same vertex. Returns the number of edges removed, counted
+    /// using the edge weights.
+    pub fn remove_self_loops(&mut self) -> usize {
+        let loops = self
+            .graph
+            .all_edges()
+            .filter(|(a, b, _)| a == b)
+            .map(|(a, _, w)| (a, w.total()))
+            .collect::<Vec<_>>();
+
+        let mut removed = 0;
+
+        for (n, total) in loops {
+            self.graph.remove_edge(n, n);
+            removed += total;
+        }
+
+        removed
+    }
+
+    /// Remove the segments that have a tip at either end, as found
+    /// by `tips`, along with all their edges. This is a single pass,
+    /// so removing a tip can leave a new one in its place. Returns the
+    /// number of segments removed.
+    pub fn prune_tips(&mut self) -> usize {
+        let mut segments = self
+            .tips()
+            .into_iter()
+            .map(|n| n.left())
+            .collect::<Vec<_>>();
+        segments.dedup();
+
+        for &left in segments.iter() {
+            self.graph.remove_node(left);
+            self.graph.remove_node(left.opposite());
+        }
+
+        segments.len()
+    }
+
     /// Merge two vertices into one, such that all the edges incident
     /// to the provided nodes are moved to be incident to the merged
     /// vertex.
@@ -547,6 +1594,45 @@ where
         Some(from)
     }
 
+    /// Merge the vertex `drop` into `keep`, like `merge_vertices`,
+    /// but with `keep` always being the vertex that remains in the
+    /// graph, and that both vertices project to afterward.
+    ///
+    /// Returns `keep`, or None if either of the provided vertices
+    /// were not present in the graph.
+    #[inline]
+    pub fn merge_vertices_into(
+        &mut self,
+        keep: Node,
+        drop: Node,
+        projection: &mut Projection,
+    ) -> Option<Node> {
+        if !self.graph.contains_node(keep) || !self.graph.contains_node(drop) {
+            return None;
+        }
+
+        projection.union_into(keep, drop);
+
+        if keep == drop {
+            return Some(keep);
+        }
+
+        let drop_edges: Vec<(Node, BiedgedWeight)> = self
+            .graph
+            .edges(drop)
+            .filter(|(_, node, _)| node != &keep && node != &drop)
+            .map(|(_, b, w)| (b, *w))
+            .collect();
+
+        self.graph.remove_node(drop);
+
+        for (other, w) in drop_edges {
+            self.add_edge(keep, other, w);
+        }
+
+        Some(keep)
+    }
+
     /// Contract a (gray) edge between two vertices.
     #[inline]
     pub fn contract_edge(
@@ -555,38 +1641,142 @@ where
         right: Node,
         projection: &mut Projection,
     ) -> Option<Node> {
+        let record = self.contract_edge_logged(left, right, projection)?;
+        Some(record.kept)
+    }
+
+    /// Contract a (gray) edge between two vertices, returning a
+    /// record of the removed vertex, the edges that were moved to the
+    /// kept vertex, and the changes to the projection, which is enough
+    /// to restore both the graph and the projection to their previous
+    /// state.
+    pub fn contract_edge_logged(
+        &mut self,
+        left: Node,
+        right: Node,
+        projection: &mut Projection,
+    ) -> Option<ContractionRecord> {
+        self.contract_edge_or_log(left, right, projection).ok()
+    }
+
+    /// Like `contract_edge_logged`, but if there's no edge to
+    /// contract, returns the changes made to the projection, as the
+    /// vertices are still merged there.
+    fn contract_edge_or_log(
+        &mut self,
+        left: Node,
+        right: Node,
+        projection: &mut Projection,
+    ) -> Result<ContractionRecord, ProjectionLog> {
+        projection.start_log();
         projection.union(left, right);
         let (from, to) = projection.kept_pair(left, right);
+        let projection_log = projection.take_log();
 
-        let weight = self.graph.edge_weight(from, to).copied()?;
+        let weight = match self.graph.edge_weight(from, to) {
+            Some(&weight) => weight,
+            None => return Err(projection_log),
+        };
+        let kept_self_weight = self.graph.edge_weight(from, from).copied();
         let other_self_weight = self.graph.edge_weight(to, to).copied();
 
         // Retrieve the edges of the node we're removing
-        let to_edges: Vec<(Node, Node, BiedgedWeight)> = self
+        let to_edges: Vec<(Node, BiedgedWeight)> = self
             .graph
             .edges(to)
             .filter(|(_, node, _)| node != &from && node != &to)
-            .map(|(a, b, w)| (a, b, *w))
+            .map(|(_, b, w)| (b, *w))
             .collect();
 
         self.graph.remove_node(to);
 
-        // add the edges that were removed with the deleted node
-        for (_, other, w) in to_edges {
+        // add the edges that were removed with the deleted node;
+        // `add_edge` sums the weights of any edges that end up
+        // parallel to an existing edge of the kept node
+        for &(other, w) in to_edges.iter() {
             self.add_edge(from, other, w);
         }
 
-        if weight.black > 0 {
-            let mut new_weight = BiedgedWeight::black(weight.black);
-            if from != to {
-                let other_black =
-                    other_self_weight.map(|w| w.black).unwrap_or_default();
-                new_weight.black += other_black
-            }
+        // the contracted black edges, and any black self-loops of the
+        // removed node, become black self-loops of the kept node
+        let mut new_weight = BiedgedWeight::black(weight.black);
+        if from != to {
+            let other_black =
+                other_self_weight.map(|w| w.black).unwrap_or_default();
+            new_weight.black += other_black
+        }
+
+        if new_weight.black > 0 {
             self.add_edge(from, from, new_weight);
         }
 
-        Some(from)
+        Ok(ContractionRecord {
+            kept: from,
+            removed: to,
+            weight,
+            kept_self_weight,
+            removed_self_weight: other_self_weight,
+            rewired: to_edges,
+            projection_log,
+        })
+    }
+
+    /// Undo a contraction using the record produced by
+    /// `contract_edge_logged`. The contractions made after it must
+    /// already have been undone. The projection is not restored; pass
+    /// the record's `projection_log` to `Projection::undo` for that.
+    pub fn undo_contraction(&mut self, record: ContractionRecord) {
+        let ContractionRecord {
+            kept,
+            removed,
+            weight,
+            kept_self_weight,
+            removed_self_weight,
+            rewired,
+            projection_log: _,
+        } = record;
+
+        self.graph.remove_edge(kept, kept);
+        if let Some(w) = kept_self_weight {
+            self.add_edge(kept, kept, w);
+        }
+
+        // contracting a self-loop doesn't move any edges
+        if kept == removed {
+            return;
+        }
+
+        for &(other, w) in rewired.iter() {
+            if let Some(edge_weight) = self.graph.edge_weight_mut(kept, other) {
+                *edge_weight -= w;
+                if edge_weight.total() == 0 {
+                    self.graph.remove_edge(kept, other);
+                }
+            }
+        }
+
+        self.graph.add_node(removed);
+        self.add_edge(kept, removed, weight);
+        if let Some(w) = removed_self_weight {
+            self.add_edge(removed, removed, w);
+        }
+        for (other, w) in rewired {
+            self.add_edge(removed, other, w);
+        }
+    }
+
+    /// Start a transaction of edge contractions on the graph and the
+    /// projection, which can be rolled back to restore both.
+    pub fn transaction<'a>(
+        &'a mut self,
+        projection: &'a mut Projection,
+    ) -> Transaction<'a, G> {
+        Transaction {
+            graph: self,
+            projection,
+            records: Vec::new(),
+            stray_logs: Vec::new(),
+        }
     }
 
     pub(crate) fn edge_count_capacity(&self) -> (usize, usize) {
@@ -602,13 +1792,80 @@ where
     }
 }
 
+/// A small SplitMix64 generator, enough to produce reproducible
+/// random graphs without depending on an RNG crate.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in the range `0..n`; `n` must be nonzero.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+/// Generate a random biedged graph with `segments` segments, each
+/// with its black edge, and `links` gray edges, each joining the
+/// sides of two random segments in random orientations, as a GFA
+/// link would. The same seed always produces the same graph.
+pub fn generate_biedged(
+    segments: usize,
+    links: usize,
+    seed: u64,
+) -> BiedgedGraph<Biedged> {
+    let mut graph: BiedgedGraph<Biedged> = Default::default();
+
+    for id in 0..segments as u64 {
+        let (left, right) = Node::from_gfa_id(id);
+        graph.add_edge(left, right, BiedgedWeight::black(1));
+    }
+
+    if segments > 0 {
+        let mut rng = SplitMix64(seed);
+
+        for _ in 0..links {
+            let from = rng.below(segments as u64);
+            let to = rng.below(segments as u64);
+            let orients = rng.next_u64();
+            graph.add_link(from, orients & 1 == 1, to, orients & 2 == 2);
+        }
+    }
+
+    let max_node = graph.graph.nodes().max().unwrap_or_default();
+    graph.max_net_vertex = max_node;
+    graph.max_chain_vertex = max_node;
+
+    graph
+}
+
+/// Generate a random biedged graph with `n_nodes` segments and
+/// `n_links` links, as used by the pipeline benchmarks. This is the
+/// same generator as `generate_biedged`, under the name the
+/// benchmarks and tests use; a seed produces the same graph through
+/// either.
+#[inline]
+pub fn random_biedged(
+    n_nodes: usize,
+    n_links: usize,
+    seed: u64,
+) -> BiedgedGraph<Biedged> {
+    generate_biedged(n_nodes, n_links, seed)
+}
+
 // ----------------------------------- TESTS -------------------------------
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[allow(dead_code)]
-    fn example_graph_2() -> BiedgedGraph<Node<Biedged>> {
+    fn example_graph_2() -> BiedgedGraph<Biedged> {
         let edges = vec![
             (0, 1),
             (0, 13),
@@ -630,106 +1887,685 @@ mod tests {
             (12, 13),
         ];
 
-        let graph = BiedgedGraph::from_directed_edges(edges).unwrap();
+        BiedgedGraph::from_directed_edges(edges).unwrap()
+    }
+
+    #[test]
+    fn weight_addition() {
+        let black = BiedgedWeight::black(2);
+        let gray = BiedgedWeight::gray(1);
 
-        graph
+        assert_eq!(black + gray, BiedgedWeight::new(2, 1));
+        assert_eq!(black + BiedgedWeight::empty(), black);
+
+        let mut sum = BiedgedWeight::empty();
+        sum += black;
+        sum += gray;
+        sum += BiedgedWeight::new(1, 3);
+        assert_eq!(sum, BiedgedWeight::new(3, 4));
+        assert_eq!(sum.total(), 7);
+
+        assert_eq!(sum - black, BiedgedWeight::new(1, 4));
+    }
+
+    #[test]
+    fn weight_predicates() {
+        let black = BiedgedWeight::black(1);
+        let gray = BiedgedWeight::gray(2);
+        let mixed = black + gray;
+        let empty = BiedgedWeight::empty();
+
+        assert!(black.black_only());
+        assert!(!black.gray_only());
+        assert_eq!(black.total(), 1);
+
+        assert!(gray.gray_only());
+        assert!(!gray.black_only());
+        assert_eq!(gray.total(), 2);
+
+        assert!(!mixed.black_only());
+        assert!(!mixed.gray_only());
+        assert_eq!(mixed.total(), 3);
+
+        assert!(!empty.black_only());
+        assert!(!empty.gray_only());
+        assert_eq!(empty.total(), 0);
+    }
+
+    #[test]
+    fn black_and_gray_degrees() {
+        let black = [(0, 1), (2, 3), (2, 3), (4, 4)];
+        let gray = [(1, 2), (1, 4), (3, 4), (3, 4)];
+
+        let graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&black, &gray);
+
+        let degrees = |n: u64| {
+            let n = Node::new(n);
+            (
+                graph.black_degree(n),
+                graph.gray_degree(n),
+                graph.total_degree(n),
+            )
+        };
+
+        assert_eq!(degrees(0), (1, 0, 1));
+        assert_eq!(degrees(1), (1, 2, 3));
+        assert_eq!(degrees(2), (2, 1, 3));
+        assert_eq!(degrees(3), (2, 2, 4));
+        assert_eq!(degrees(4), (2, 3, 5));
+        assert_eq!(degrees(5), (0, 0, 0));
+    }
+
+    #[test]
+    fn overlarge_segment_ids() {
+        let max = u64::MAX / 2;
+
+        assert_eq!(
+            Node::try_from_gfa_id(max),
+            Some((Node::new(u64::MAX - 1), Node::new(u64::MAX)))
+        );
+        assert_eq!(Node::try_from_gfa_id(max + 1), None);
+        assert_eq!(Node::try_from_gfa_id(7), Some(Node::from_gfa_id(7)));
+
+        let mut gfa: GFA<usize, ()> = GFA::new();
+        for &name in [0, (max + 1) as usize].iter() {
+            gfa.segments.push(Segment {
+                name,
+                sequence: b"A".to_vec(),
+                optional: (),
+            });
+        }
+
+        let result = BiedgedGraph::<Biedged>::try_from_gfa(&gfa);
+        assert_eq!(
+            result.err(),
+            Some(BiedgedError::SegmentIdOverflow((max + 1) as usize))
+        );
+
+        let gfa_text = format!("S\t0\tA\nS\t{}\tA\n", max + 1);
+        let result =
+            BiedgedGraph::<Biedged>::from_gfa_reader(gfa_text.as_bytes());
+        assert_eq!(
+            result.err().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidData)
+        );
+    }
+
+    #[test]
+    fn links_to_missing_segments() {
+        use gfa::gfa::Link;
+
+        let mut gfa: GFA<usize, ()> = GFA::new();
+        for &name in [1, 2].iter() {
+            gfa.segments.push(Segment {
+                name,
+                sequence: b"A".to_vec(),
+                optional: (),
+            });
+        }
+
+        let link = |from: usize, to: usize| Link {
+            from_segment: from,
+            from_orient: Orientation::Forward,
+            to_segment: to,
+            to_orient: Orientation::Forward,
+            overlap: b"0M".to_vec(),
+            optional: (),
+        };
+
+        gfa.links.push(link(1, 2));
+        assert!(BiedgedGraph::<Biedged>::try_from_gfa(&gfa).is_ok());
+
+        gfa.links.push(link(2, 3));
+        let result = BiedgedGraph::<Biedged>::try_from_gfa(&gfa);
+        assert_eq!(
+            result.err(),
+            Some(BiedgedError::MissingSegment {
+                link: 1,
+                segment: 3
+            })
+        );
+    }
+
+    #[test]
+    fn graph_equality() {
+        let black = [(0, 1), (2, 3), (4, 5)];
+        let gray = [(1, 2), (1, 4), (3, 4)];
+
+        let graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&black, &gray);
+
+        let mut reversed_black = black;
+        reversed_black.reverse();
+        let reversed_gray =
+            gray.iter().rev().map(|&(a, b)| (b, a)).collect::<Vec<_>>();
+        let reversed: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&reversed_black, &reversed_gray);
+
+        assert!(graph == reversed);
+        assert_eq!(graph.canonical_hash(), reversed.canonical_hash());
+
+        let mut extra = graph.clone();
+        extra.add_edge(Node::new(3), Node::new(4), BiedgedWeight::gray(1));
+        assert!(graph != extra);
+        assert_ne!(graph.canonical_hash(), extra.canonical_hash());
+
+        let mut isolated = graph.clone();
+        isolated.add_node(6);
+        assert!(graph != isolated);
+    }
+
+    #[test]
+    fn remove_self_loops() {
+        let black = [(0, 1), (2, 3), (4, 5), (2, 2)];
+        let gray = [(1, 2), (3, 4), (5, 5), (5, 5), (2, 2)];
+
+        let mut graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&black, &gray);
+
+        assert_eq!(graph.remove_self_loops(), 4);
+        assert_eq!(graph.remove_self_loops(), 0);
+
+        let mut edges = graph
+            .edges()
+            .map(|(a, b, w)| (a.min(b), a.max(b), w.black, w.gray))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+
+        assert_eq!(
+            edges,
+            vec![
+                (0, 1, 1, 0),
+                (1, 2, 0, 1),
+                (2, 3, 1, 0),
+                (3, 4, 0, 1),
+                (4, 5, 1, 0)
+            ]
+        );
+        assert_eq!(graph.node_count(), 6);
+    }
+
+    #[test]
+    fn graph_from_edges() {
+        let black = [(0, 1), (2, 3), (4, 5), (4, 5)];
+        let gray = [(1, 2), (1, 4), (3, 4)];
+
+        let graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&black, &gray);
+
+        let mut manual: BiedgedGraph<Biedged> = BiedgedGraph::default();
+        for i in 0..=5 {
+            manual.add_node(i);
+        }
+        for &(a, b) in black.iter() {
+            manual.add_edge(a.into(), b.into(), BiedgedWeight::black(1));
+        }
+        for &(a, b) in gray.iter() {
+            manual.add_edge(a.into(), b.into(), BiedgedWeight::gray(1));
+        }
+
+        let edge_set = |g: &BiedgedGraph<Biedged>| {
+            let mut edges = g
+                .edges()
+                .map(|(a, b, w)| (a.min(b), a.max(b), w.black, w.gray))
+                .collect::<Vec<_>>();
+            edges.sort_unstable();
+            edges
+        };
+
+        assert_eq!(edge_set(&graph), edge_set(&manual));
+        assert_eq!(
+            edge_set(&graph),
+            vec![
+                (0, 1, 1, 0),
+                (1, 2, 0, 1),
+                (1, 4, 0, 1),
+                (2, 3, 1, 0),
+                (3, 4, 0, 1),
+                (4, 5, 2, 0)
+            ]
+        );
+
+        assert_eq!(graph.node_count(), 6);
+        assert_eq!(graph.black_edge_count(), 4);
+        assert_eq!(graph.gray_edge_count(), 3);
+        assert_eq!(graph.max_net_vertex, Node::new(5));
+        assert!(graph.net_vertices().map(|n| n.id).eq(manual.node_ids()));
+    }
+
+    #[test]
+    fn bridges_cycle_and_path() {
+        let black = [(0, 1), (2, 3), (4, 5)];
+
+        let cycle: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&black, &[(1, 2), (3, 4), (5, 0)]);
+        assert!(cycle.bridges().is_empty());
+
+        let path: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&black, &[(1, 2), (3, 4)]);
+        assert_eq!(
+            path.bridges(),
+            vec![
+                (0.into(), 1.into()),
+                (2.into(), 3.into()),
+                (4.into(), 5.into())
+            ]
+        );
+
+        // Doubling up an edge on the path means it's no longer a bridge
+        let doubled: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&[(0, 1), (2, 3), (2, 3)], &[(1, 2)]);
+        assert_eq!(doubled.bridges(), vec![(0.into(), 1.into())]);
+    }
+
+    #[test]
+    fn cut_vertices_cycle_and_path() {
+        let black = [(0, 1), (2, 3), (4, 5)];
+
+        let cycle: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&black, &[(1, 2), (3, 4), (5, 0)]);
+        assert!(cycle.cut_vertices().is_empty());
+
+        let path: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&black, &[(1, 2), (3, 4)]);
+        let expected = [1, 2, 3, 4].iter().map(|&n| Node::new(n)).collect();
+        assert_eq!(path.cut_vertices(), expected);
+
+        // Two cycles sharing the vertex 0
+        let mut figure_eight = cycle.clone();
+        figure_eight.add_edge(0.into(), 6.into(), BiedgedWeight::black(1));
+        figure_eight.add_edge(6.into(), 0.into(), BiedgedWeight::gray(1));
+        assert_eq!(
+            figure_eight.cut_vertices(),
+            std::iter::once(Node::new(0)).collect()
+        );
+    }
+
+    #[test]
+    fn contraction_folds_parallel_edges() {
+        // Two black edges from 0 and 1 to 2, and a gray edge between
+        // 0 and 1; 1 also has a black self-loop
+        let mut graph: BiedgedGraph<Biedged> = BiedgedGraph::from_edges(
+            &[(0, 2), (1, 2), (1, 1)],
+            &[(0, 1), (0, 3), (1, 3)],
+        );
+        let mut proj = Projection::new_for_biedged_graph(&graph);
+
+        let kept = graph.contract_edge(0.into(), 1.into(), &mut proj).unwrap();
+        let other = proj.find(2.into());
+
+        let w = graph.edge_weight(kept, other).unwrap();
+        assert_eq!(w, BiedgedWeight::black(2));
+
+        let w = graph.edge_weight(kept, 3.into()).unwrap();
+        assert_eq!(w, BiedgedWeight::gray(2));
+
+        let w = graph.edge_weight(kept, kept).unwrap();
+        assert_eq!(w, BiedgedWeight::black(1));
+
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.black_edge_count(), 3);
+        assert_eq!(graph.gray_edge_count(), 2);
+    }
+
+    #[test]
+    fn streaming_gfa_matches_parsed() {
+        use gfa::parser::GFAParser;
+        use std::{fs::File, io::BufReader};
+
+        let sorted_edges = |g: &BiedgedGraph<Biedged>| {
+            let mut edges = g
+                .edges()
+                .map(|(a, b, w)| (a.min(b), a.max(b), w.black, w.gray))
+                .collect::<Vec<_>>();
+            edges.sort_unstable();
+            edges
+        };
+
+        for path in
+            ["./test/gfas/paper_u64.gfa", "./test/gfas/A-3105.gfa"].iter()
+        {
+            let parser: GFAParser<usize, ()> = GFAParser::new();
+            let gfa = parser.parse_file(path).unwrap();
+            let parsed: BiedgedGraph<Biedged> = BiedgedGraph::from_gfa(&gfa);
+
+            let reader = BufReader::new(File::open(path).unwrap());
+            let streamed: BiedgedGraph<Biedged> =
+                BiedgedGraph::from_gfa_reader(reader).unwrap();
+
+            assert_eq!(streamed.node_count(), parsed.node_count());
+            assert_eq!(sorted_edges(&streamed), sorted_edges(&parsed));
+            assert_eq!(streamed.max_net_vertex, parsed.max_net_vertex);
+        }
+
+        let bad_link = "S\t1\t*\nL\t1\t+\tx\t+\t0M\n";
+        let res = BiedgedGraph::<Biedged>::from_gfa_reader(bad_link.as_bytes());
+        assert_eq!(res.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rgfa_segment_names() {
+        use crate::projection::projected_node_name;
+        use gfa::parser::GFAParser;
+
+        let gfa = "S\ts1\t*\nS\ts2\t*\nS\ts3\t*\n\
+                   L\ts1\t+\ts2\t+\t0M\nL\ts1\t+\ts3\t-\t0M\n\
+                   P\tp\ts1+,s3-\t*\n";
+
+        assert!(
+            BiedgedGraph::<Biedged>::from_gfa_reader(gfa.as_bytes()).is_err()
+        );
+
+        let parser = GFAParser::new();
+        let vec_gfa: GFA<Vec<u8>, ()> = parser
+            .parse_lines(gfa.lines().map(|l| l.as_bytes()))
+            .unwrap();
+
+        let naming = NamingScheme::rgfa();
+        assert!(NamingScheme::Integer.gfa_to_usize(&vec_gfa).is_none());
+
+        let usize_gfa = naming.gfa_to_usize(&vec_gfa).unwrap();
+        let path = usize_gfa.paths[0].iter().collect::<Vec<_>>();
+        assert_eq!(
+            path,
+            vec![(1, Orientation::Forward), (3, Orientation::Backward)]
+        );
+
+        let mut graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_gfa(&usize_gfa);
+
+        let streamed: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_gfa_reader_named(gfa.as_bytes(), &naming)
+                .unwrap();
+        assert!(streamed == graph);
+
+        let mut nodes = graph.node_ids().collect::<Vec<_>>();
+        nodes.sort_unstable();
+        assert_eq!(nodes, vec![2, 3, 4, 5, 6, 7]);
+        assert_eq!(
+            graph.edge_weight(Node::new(3), Node::new(7)),
+            Some(BiedgedWeight::gray(1))
+        );
+
+        // contracting the link from s1+ to s3- leaves the right-hand
+        // side of s1 in its place
+        let mut projection = Projection::new_for_biedged_graph(&graph);
+        graph.contract_edge(Node::new(3), Node::new(7), &mut projection);
+
+        let projected = projection.find(Node::new(7));
+        let name = projected_node_name(&naming, projected).unwrap();
+        assert_eq!(name, "s1_");
+
+        let segment = name.strip_suffix(b"_").unwrap();
+        let segment = std::str::from_utf8(segment).unwrap();
+        assert_eq!(naming.parse_name(segment), Some(1));
+        assert_eq!(naming.segment_name(1), segment);
+
+        assert_eq!(projected_node_name(&naming, Node::new(4)).unwrap(), "s2");
+        assert_eq!(naming.parse_name("2"), None);
+        assert_eq!(NamingScheme::Integer.parse_name("2"), Some(2));
+    }
+
+    #[test]
+    fn add_link_orientations() {
+        let segments = "S\t1\t*\nS\t2\t*\n";
+
+        let mut graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_gfa_reader(segments.as_bytes()).unwrap();
+
+        // 1+ -> 2- connects the right-hand sides of both segments
+        let (from, to) = graph.add_link(1, false, 2, true);
+        assert_eq!((from, to), (Node::new(3), Node::new(5)));
+        assert_eq!(
+            graph.edge_weight(3.into(), 5.into()),
+            Some(BiedgedWeight::gray(1))
+        );
+        assert_eq!(graph.gray_edge_count(), 1);
+
+        let orients = [("+", false), ("-", true)];
+
+        for &(from_o, from_rev) in orients.iter() {
+            for &(to_o, to_rev) in orients.iter() {
+                let gfa =
+                    format!("{}L\t1\t{}\t2\t{}\t0M\n", segments, from_o, to_o);
+                let parsed: BiedgedGraph<Biedged> =
+                    BiedgedGraph::from_gfa_reader(gfa.as_bytes()).unwrap();
+
+                let mut linked: BiedgedGraph<Biedged> =
+                    BiedgedGraph::from_gfa_reader(segments.as_bytes()).unwrap();
+                linked.add_link(1, from_rev, 2, to_rev);
+
+                assert!(linked == parsed);
+            }
+        }
+    }
+
+    #[test]
+    fn paper_graph_accessors() {
+        use gfa::parser::GFAParser;
+
+        let parser: GFAParser<usize, ()> = GFAParser::new();
+        let gfa = parser.parse_file("./test/gfas/paper_u64.gfa").unwrap();
+        let graph: BiedgedGraph<Biedged> = BiedgedGraph::from_gfa(&gfa);
+
+        let mut node_ids = graph.node_ids().collect::<Vec<_>>();
+        node_ids.sort_unstable();
+        assert_eq!(node_ids, (0..36).collect::<Vec<_>>());
+        assert_eq!(graph.node_count(), 36);
+
+        let edges = graph.edges().collect::<Vec<_>>();
+        assert_eq!(edges.len(), graph.edge_count());
+
+        let black = edges.iter().map(|(_, _, w)| w.black).sum::<usize>();
+        let gray = edges.iter().map(|(_, _, w)| w.gray).sum::<usize>();
+        assert_eq!(black, graph.black_edge_count());
+        assert_eq!(gray, graph.gray_edge_count());
+
+        for &(a, b, w) in edges.iter() {
+            let (a, b) = (Node::new(a), Node::new(b));
+            assert!(graph.contains_node(a) && graph.contains_node(b));
+            assert_eq!(graph.edge_weight(a, b), Some(w));
+            assert_eq!(graph.edge_weight(b, a), Some(w));
+        }
+
+        assert!(graph
+            .edges()
+            .all(|(a, b, w)| w.black == 0
+                || Node::new(a).opposite() == Node::new(b)));
+
+        assert!(!graph.contains_node(Node::new(36)));
+        assert_eq!(graph.edge_weight(Node::new(2), Node::new(5)), None);
+    }
+
+    #[test]
+    fn random_graphs_are_reproducible() {
+        let graph = random_biedged(100, 150, 7);
+
+        assert_eq!(graph.node_count(), 200);
+        assert_eq!(graph.black_edge_count(), 100);
+        assert_eq!(graph.gray_edge_count(), 150);
+        assert_eq!(graph.max_net_vertex, Node::new(199));
+
+        assert!(graph == random_biedged(100, 150, 7));
+        assert!(graph != random_biedged(100, 150, 8));
+
+        assert_eq!(random_biedged(0, 10, 7).node_count(), 0);
+        assert!(graph == generate_biedged(100, 150, 7));
+    }
+
+    #[test]
+    fn generated_graphs_are_valid() {
+        for seed in 0..20 {
+            let graph = generate_biedged(50, 80, seed);
+            assert_eq!(graph.validate(), Ok(()));
+
+            let mut graph = graph.set_graph_type::<crate::snarls::Cactus>();
+            let mut proj = Projection::new_for_biedged_graph(&graph);
+            crate::cactusgraph::CactusGraph::contract_all_gray_edges(
+                &mut graph, &mut proj,
+            );
+            assert!(graph.is_cactus_ready());
+            assert_eq!(graph.black_edge_count(), 50);
+        }
+
+        let mut graph = generate_biedged(3, 0, 0);
+        assert_eq!(graph.validate(), Ok(()));
+
+        graph.add_edge(1.into(), 2.into(), BiedgedWeight::black(1));
+        assert_eq!(graph.validate(), Err(Node::new(1)));
+
+        let mut graph = generate_biedged(3, 0, 0);
+        graph.graph.remove_edge(2.into(), 3.into());
+        assert_eq!(graph.validate(), Err(Node::new(2)));
+    }
+
+    #[test]
+    fn black_endpoints() {
+        let mut graph = generate_biedged(2, 1, 0);
+        graph.add_edge(1.into(), 5.into(), BiedgedWeight::black(1));
+
+        assert!((0..4).all(|n| graph.is_black_endpoint(n.into())));
+        assert!(!graph.is_black_endpoint(5.into()));
+        assert!(!graph.is_black_endpoint(6.into()));
+    }
+
+    #[test]
+    fn detect_and_prune_tips() {
+        // a bubble from 1 to 4 that loops back to 1, with segment 5
+        // hanging off 2, and segment 6 leading into 3
+        let gfa = "S\t1\t*\nS\t2\t*\nS\t3\t*\nS\t4\t*\nS\t5\t*\nS\t6\t*\n\
+                   L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\n\
+                   L\t2\t+\t4\t+\t0M\nL\t3\t+\t4\t+\t0M\n\
+                   L\t4\t+\t1\t+\t0M\nL\t2\t+\t5\t+\t0M\n\
+                   L\t6\t+\t3\t+\t0M\n";
+
+        let mut graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_gfa_reader(gfa.as_bytes()).unwrap();
+
+        // the right-hand side of 5 and the left-hand side of 6
+        assert_eq!(graph.tips(), vec![Node::new(11), Node::new(12)]);
+
+        assert_eq!(graph.prune_tips(), 2);
+        assert!(graph.tips().is_empty());
+        assert_eq!(graph.node_count(), 8);
+        assert_eq!(graph.gray_edge_count(), 5);
+        assert!(!graph.contains_node(Node::new(10)));
+        assert!(!graph.contains_node(Node::new(13)));
+
+        assert_eq!(graph.prune_tips(), 0);
+    }
+
+    #[test]
+    fn empty_graphs() {
+        let graph: BiedgedGraph<Biedged> = BiedgedGraph::default();
+        assert!(graph.is_empty());
+
+        let graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_gfa_reader("H\tVN:Z:1.0\n".as_bytes()).unwrap();
+        assert!(graph.is_empty());
+        assert!(graph.validate().is_ok());
+        assert!(graph.tips().is_empty());
+
+        let graph = generate_biedged(1, 0, 0);
+        assert!(!graph.is_empty());
     }
 
     #[test]
     fn test_add_node() {
-        let mut graph: BiedgedGraph = BiedgedGraph::new();
+        let mut graph: BiedgedGraph<Biedged> = BiedgedGraph::default();
         graph.add_node(10);
-        assert!(graph.graph.contains_node(10));
-        assert!(graph.graph.node_count() == 1);
+        assert!(graph.contains_node(10.into()));
+        assert!(graph.node_count() == 1);
     }
 
     #[test]
     fn test_add_edge() {
-        let mut graph: BiedgedGraph = BiedgedGraph::new();
+        let mut graph: BiedgedGraph<Biedged> = BiedgedGraph::default();
         graph.add_node(0);
         graph.add_node(1);
         graph.add_node(2);
 
-        graph.add_edge(0, 1, BiedgedWeight::black(1));
-        assert!(graph.graph.contains_edge(0, 1));
+        graph.add_edge(0.into(), 1.into(), BiedgedWeight::black(1));
+        assert!(graph.edge_weight(0.into(), 1.into()).is_some());
 
         assert_eq!(graph.black_edge_count(), 1);
         assert_eq!(
-            Some(&BiedgedWeight { black: 1, gray: 0 }),
-            graph.graph.edge_weight(0, 1)
+            Some(BiedgedWeight { black: 1, gray: 0 }),
+            graph.edge_weight(0.into(), 1.into())
         );
 
-        graph.add_edge(1, 2, BiedgedWeight::gray(1));
-        assert!(graph.graph.contains_edge(1, 2));
+        graph.add_edge(1.into(), 2.into(), BiedgedWeight::gray(1));
+        assert!(graph.edge_weight(1.into(), 2.into()).is_some());
         assert_eq!(graph.gray_edge_count(), 1);
 
         assert_eq!(
-            Some(&BiedgedWeight { black: 0, gray: 1 }),
-            graph.graph.edge_weight(1, 2)
+            Some(BiedgedWeight { black: 0, gray: 1 }),
+            graph.edge_weight(1.into(), 2.into())
         );
 
-        graph.add_edge(1, 2, BiedgedWeight::black(1));
+        graph.add_edge(1.into(), 2.into(), BiedgedWeight::black(1));
 
         assert_eq!(
-            Some(&BiedgedWeight { black: 1, gray: 1 }),
-            graph.graph.edge_weight(1, 2)
+            Some(BiedgedWeight { black: 1, gray: 1 }),
+            graph.edge_weight(1.into(), 2.into())
         );
     }
 
     #[test]
     fn contract_one_edge() {
-        let mut graph: BiedgedGraph = BiedgedGraph::new();
+        let mut graph: BiedgedGraph<Biedged> = BiedgedGraph::default();
         graph.add_node(0);
         graph.add_node(1);
         graph.add_node(2);
-        graph.add_edge(0, 1, BiedgedWeight::black(1));
-        graph.add_edge(0, 2, BiedgedWeight::gray(1));
-        graph.add_edge(1, 2, BiedgedWeight::black(1));
+        graph.add_edge(0.into(), 1.into(), BiedgedWeight::black(1));
+        graph.add_edge(0.into(), 2.into(), BiedgedWeight::gray(1));
+        graph.add_edge(1.into(), 2.into(), BiedgedWeight::black(1));
 
-        graph.max_net_vertex = graph.graph.node_count() as u64;
+        graph.max_net_vertex = Node::from(graph.node_count() as u64);
 
         let mut proj = Projection::new_for_biedged_graph(&graph);
 
-        assert_eq!(None, graph.graph.edge_weight(0, 0));
+        assert_eq!(None, graph.edge_weight(0.into(), 0.into()));
         assert_eq!(
-            Some(&BiedgedWeight { black: 0, gray: 1 }),
-            graph.graph.edge_weight(0, 2)
+            Some(BiedgedWeight { black: 0, gray: 1 }),
+            graph.edge_weight(0.into(), 2.into())
         );
         assert_eq!(
-            Some(&BiedgedWeight { black: 1, gray: 0 }),
-            graph.graph.edge_weight(0, 1)
+            Some(BiedgedWeight { black: 1, gray: 0 }),
+            graph.edge_weight(0.into(), 1.into())
         );
         assert_eq!(
-            Some(&BiedgedWeight { black: 1, gray: 0 }),
-            graph.graph.edge_weight(1, 2)
+            Some(BiedgedWeight { black: 1, gray: 0 }),
+            graph.edge_weight(1.into(), 2.into())
         );
 
-        graph.contract_edge(0, 1, &mut proj);
+        graph.contract_edge(0.into(), 1.into(), &mut proj);
 
         assert_eq!(
-            Some(&BiedgedWeight { black: 1, gray: 0 }),
-            graph.graph.edge_weight(0, 0)
+            Some(BiedgedWeight { black: 1, gray: 0 }),
+            graph.edge_weight(0.into(), 0.into())
         );
         assert_eq!(
-            Some(&BiedgedWeight { black: 1, gray: 1 }),
-            graph.graph.edge_weight(0, 2)
+            Some(BiedgedWeight { black: 1, gray: 1 }),
+            graph.edge_weight(0.into(), 2.into())
         );
-        assert_eq!(None, graph.graph.edge_weight(0, 1));
-        assert_eq!(None, graph.graph.edge_weight(1, 2));
+        assert_eq!(None, graph.edge_weight(0.into(), 1.into()));
+        assert_eq!(None, graph.edge_weight(1.into(), 2.into()));
 
-        assert!(graph.graph.contains_node(0));
-        assert!(graph.graph.contains_node(2));
-        assert!(!graph.graph.contains_node(1));
+        assert!(graph.contains_node(0.into()));
+        assert!(graph.contains_node(2.into()));
+        assert!(!graph.contains_node(1.into()));
 
-        assert!(graph.graph.edge_count() == 2);
+        assert!(graph.edge_count() == 2);
 
         assert_eq!(graph.black_edge_count(), 2);
         assert_eq!(graph.gray_edge_count(), 1);
 
-        assert!(proj.equiv(0, 1));
+        assert!(proj.equiv(0.into(), 1.into()));
 
-        for i in 2..=3 {
-            assert!(!proj.equiv(0, i as u64));
+        for i in 2..=3u64 {
+            assert!(!proj.equiv(0.into(), i.into()));
         }
     }
 
@@ -741,54 +2577,54 @@ mod tests {
         let mut graph = BiedgedGraph::from_directed_edges(edges).unwrap();
         let mut proj = Projection::new_for_biedged_graph(&graph);
 
-        graph.contract_edge(1, 2, &mut proj);
-        let (x, y) = proj.kept_pair(1, 2);
+        graph.contract_edge(1.into(), 2.into(), &mut proj);
+        let (x, y) = proj.kept_pair(1.into(), 2.into());
 
         // One of the two nodes were deleted
-        assert!(graph.graph.contains_node(x));
-        assert!(!graph.graph.contains_node(y));
+        assert!(graph.contains_node(x));
+        assert!(!graph.contains_node(y));
 
-        graph.contract_edge(4, 1, &mut proj);
+        graph.contract_edge(4.into(), 1.into(), &mut proj);
 
-        let (x_, y_) = proj.kept_pair(4, 1);
+        let (x_, y_) = proj.kept_pair(4.into(), 1.into());
 
         // The kept node must be the same in both cases, as one node
         // was included in both contractions
         assert_eq!(x, x_);
 
-        assert!(graph.graph.contains_node(x_));
-        assert!(!graph.graph.contains_node(y_));
-        assert!(!graph.graph.contains_node(y));
+        assert!(graph.contains_node(x_));
+        assert!(!graph.contains_node(y_));
+        assert!(!graph.contains_node(y));
 
         let first_union: Vec<u64> = vec![1, 2, 4];
 
         // All combinations of contracted edges have the same projection
-        assert!(proj.equiv(1, 2));
-        assert!(proj.equiv(1, 4));
-        assert!(proj.equiv(2, 4));
+        assert!(proj.equiv(1.into(), 2.into()));
+        assert!(proj.equiv(1.into(), 4.into()));
+        assert!(proj.equiv(2.into(), 4.into()));
 
-        let edges_vec = |g: &BiedgedGraph, x: u64| {
+        let edges_vec = |g: &BiedgedGraph<Biedged>, x: Node| {
             g.graph
                 .edges(x)
-                .map(|(a, b, w)| (a, b, w.black, w.gray))
+                .map(|(a, b, w)| (a.id, b.id, w.black, w.gray))
                 .collect::<Vec<_>>()
         };
 
-        let x = proj.find(4);
+        let x = proj.find(4.into());
         let edges = edges_vec(&graph, x);
 
         assert_eq!(edges, vec![(1, 0, 1, 0), (1, 3, 1, 0), (1, 5, 1, 0)]);
 
-        graph.contract_edge(7, 8, &mut proj);
-        graph.contract_edge(0, 7, &mut proj);
+        graph.contract_edge(7.into(), 8.into(), &mut proj);
+        graph.contract_edge(0.into(), 7.into(), &mut proj);
 
         let second_union: Vec<u64> = vec![0, 7, 8];
 
-        assert!(proj.equiv(0, 7));
-        assert!(proj.equiv(7, 8));
-        assert!(proj.equiv(0, 8));
+        assert!(proj.equiv(0.into(), 7.into()));
+        assert!(proj.equiv(7.into(), 8.into()));
+        assert!(proj.equiv(0.into(), 8.into()));
 
-        let x = proj.find(7);
+        let x = proj.find(7.into());
         let edges = edges_vec(&graph, x);
 
         assert_eq!(
@@ -796,25 +2632,206 @@ mod tests {
             vec![(7, 6, 1, 0), (7, 9, 1, 0), (7, 10, 0, 1), (7, 1, 1, 0)]
         );
 
-        graph.contract_edge(0, 1, &mut proj);
+        graph.contract_edge(0.into(), 1.into(), &mut proj);
 
-        let (x_2, y_2) = proj.kept_pair(8, 4);
+        let (x_2, y_2) = proj.kept_pair(8.into(), 4.into());
 
         assert_eq!(x, x_2);
 
-        assert!(graph.graph.contains_node(x_2));
-        assert!(!graph.graph.contains_node(y));
-        assert!(!graph.graph.contains_node(y_));
-        assert!(!graph.graph.contains_node(y_2));
+        assert!(graph.contains_node(x_2));
+        assert!(!graph.contains_node(y));
+        assert!(!graph.contains_node(y_));
+        assert!(!graph.contains_node(y_2));
 
         // Now all nodes in the contracted edges have been unified
         for (a, b) in first_union.iter().zip(second_union.iter()) {
-            let x = proj.find(*a);
-            let y = proj.find(*b);
+            let x = proj.find(Node::from(*a));
+            let y = proj.find(Node::from(*b));
             assert_eq!(x, y);
         }
     }
 
+    #[test]
+    fn undo_logged_contraction() {
+        let edges =
+            vec![(0, 1), (0, 2), (1, 3), (2, 3), (3, 4), (3, 5), (3, 0)];
+
+        let mut graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_directed_edges(edges).unwrap();
+        let mut proj = Projection::new_for_biedged_graph(&graph);
+
+        let sorted_edges = |g: &BiedgedGraph<Biedged>| {
+            let mut edges = g
+                .edges()
+                .map(|(a, b, w)| (a.min(b), a.max(b), w.black, w.gray))
+                .collect::<Vec<_>>();
+            edges.sort_unstable();
+            edges
+        };
+
+        // contract a gray and a black edge first, so the kept vertex
+        // has a self-loop
+        graph.contract_edge(1.into(), 2.into(), &mut proj);
+        graph.contract_edge(proj.find(2.into()), 3.into(), &mut proj);
+
+        let before = sorted_edges(&graph);
+        let node_count = graph.node_count();
+        let proj_before = proj.projection_map();
+
+        let record = graph
+            .contract_edge_logged(proj.find(2.into()), 6.into(), &mut proj)
+            .unwrap();
+
+        assert!(!graph.contains_node(record.removed));
+        assert!(graph.contains_node(record.kept));
+        assert_eq!(record.weight, BiedgedWeight::gray(1));
+        assert_eq!(record.kept_self_weight, Some(BiedgedWeight::black(1)));
+        assert_ne!(sorted_edges(&graph), before);
+
+        let ContractionRecord {
+            kept,
+            removed,
+            weight,
+            kept_self_weight,
+            removed_self_weight,
+            rewired,
+            projection_log,
+        } = record;
+
+        for &(other, w) in rewired.iter() {
+            let edge_weight = graph.graph.edge_weight_mut(kept, other).unwrap();
+            *edge_weight -= w;
+            if edge_weight.total() == 0 {
+                graph.graph.remove_edge(kept, other);
+            }
+        }
+
+        graph.graph.remove_edge(kept, kept);
+        if let Some(w) = kept_self_weight {
+            graph.add_edge(kept, kept, w);
+        }
+
+        graph.graph.add_node(removed);
+        graph.add_edge(kept, removed, weight);
+        if let Some(w) = removed_self_weight {
+            graph.add_edge(removed, removed, w);
+        }
+        for (other, w) in rewired {
+            graph.add_edge(removed, other, w);
+        }
+
+        assert_eq!(graph.node_count(), node_count);
+        assert_eq!(sorted_edges(&graph), before);
+
+        assert_ne!(proj.projection_map(), proj_before);
+        proj.undo(projection_log);
+        assert_eq!(proj.projection_map(), proj_before);
+    }
+
+    #[test]
+    fn contraction_chain_keeps_sides() {
+        // A path through four segments, where each gray edge joins the
+        // right-hand side of one segment to the left-hand side of the
+        // next
+        let black = [(0, 1), (2, 3), (4, 5), (6, 7)];
+        let gray = [(1, 2), (3, 4), (5, 6)];
+
+        let mut graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&black, &gray);
+        let mut proj = Projection::new_for_biedged_graph(&graph);
+
+        for &(a, b) in gray.iter() {
+            let (a, b) = (Node::new(a), Node::new(b));
+            let record = graph.contract_edge_logged(a, b, &mut proj).unwrap();
+
+            // the kept vertex is one of the two, with its ID unchanged
+            assert!(record.kept == a || record.kept == b);
+            assert_ne!(record.kept.is_left(), record.removed.is_left());
+
+            let (projected, same_side) = proj.find_with_side(a);
+            assert_eq!(projected, record.kept);
+            assert_eq!(same_side, record.kept == a);
+
+            let (projected, same_side) = proj.find_with_side(b);
+            assert_eq!(projected, record.kept);
+            assert_eq!(same_side, record.kept == b);
+        }
+
+        // the outer ends of the path were never contracted
+        assert_eq!(proj.find_with_side(0.into()), (0.into(), true));
+        assert_eq!(proj.find_with_side(7.into()), (7.into(), true));
+
+        // each contracted pair projects to one of its vertices, so
+        // exactly one vertex in each pair is on the same side as its
+        // projection
+        assert_eq!(graph.node_count(), 5);
+
+        for &(a, b) in gray.iter() {
+            let (proj_a, same_a) = proj.find_with_side(a.into());
+            let (proj_b, same_b) = proj.find_with_side(b.into());
+            assert_eq!(proj_a, proj_b);
+            assert!(same_a != same_b);
+        }
+    }
+
+    #[test]
+    fn transaction_rollback() {
+        let edges =
+            vec![(0, 1), (0, 2), (1, 3), (2, 3), (3, 4), (3, 5), (3, 0)];
+
+        let original: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_directed_edges(edges).unwrap();
+
+        let mut graph = original.clone();
+        let mut proj = Projection::new_for_biedged_graph(&graph);
+        let original_proj = proj.projection_map();
+
+        let contract_three = |tx: &mut Transaction<'_, Biedged>| {
+            let kept = tx.contract_edge(1.into(), 2.into()).unwrap();
+            let kept = tx.contract_edge(kept, 4.into()).unwrap();
+            tx.contract_edge(kept, 3.into()).unwrap();
+        };
+
+        let mut tx = graph.transaction(&mut proj);
+        contract_three(&mut tx);
+        assert_eq!(tx.records().len(), 3);
+        assert!(tx.records().iter().all(|r| !r.projection_log.is_empty()));
+        assert!(tx.graph() != &original);
+        tx.rollback();
+
+        assert!(graph == original);
+        assert_eq!(proj.projection_map(), original_proj);
+
+        // there's no edge between 0 and 5, but contracting it still
+        // merges them in the projection, which is also rolled back
+        let mut tx = graph.transaction(&mut proj);
+        assert_eq!(tx.contract_edge(0.into(), 5.into()), None);
+        assert!(tx.projection().equiv(0.into(), 5.into()));
+        contract_three(&mut tx);
+        assert_eq!(tx.records().len(), 3);
+        tx.rollback();
+
+        assert!(graph == original);
+        assert_eq!(proj.projection_map(), original_proj);
+
+        // dropping an uncommitted transaction also rolls it back
+        {
+            let mut tx = graph.transaction(&mut proj);
+            contract_three(&mut tx);
+        }
+
+        assert!(graph == original);
+        assert_eq!(proj.projection_map(), original_proj);
+
+        let mut tx = graph.transaction(&mut proj);
+        contract_three(&mut tx);
+        tx.commit();
+
+        assert!(graph != original);
+        assert_eq!(graph.node_count(), original.node_count() - 3);
+        assert!(proj.equiv(1.into(), 4.into()));
+    }
+
     #[test]
     fn merge_two_vertices() {
         let edges =
@@ -823,20 +2840,20 @@ mod tests {
         let mut graph = BiedgedGraph::from_directed_edges(edges).unwrap();
         let mut proj = Projection::new_for_biedged_graph(&graph);
 
-        graph.merge_vertices(7, 8, &mut proj);
-        graph.merge_vertices(7, 9, &mut proj);
+        graph.merge_vertices(7.into(), 8.into(), &mut proj);
+        graph.merge_vertices(7.into(), 9.into(), &mut proj);
 
-        let (x, _y) = proj.kept_pair(7, 9);
+        let (x, _y) = proj.kept_pair(7.into(), 9.into());
 
-        let edges_vec = |g: &BiedgedGraph, x: u64| {
+        let edges_vec = |g: &BiedgedGraph<Biedged>, x: Node| {
             g.graph
                 .edges(x)
-                .map(|(a, b, w)| (a, b, w.black, w.gray))
+                .map(|(a, b, w)| (a.id, b.id, w.black, w.gray))
                 .collect::<Vec<_>>()
         };
 
-        graph.merge_vertices(0, 7, &mut proj);
-        graph.merge_vertices(1, 7, &mut proj);
+        graph.merge_vertices(0.into(), 7.into(), &mut proj);
+        graph.merge_vertices(1.into(), 7.into(), &mut proj);
 
         let edges = edges_vec(&graph, x);
 
@@ -848,12 +2865,75 @@ mod tests {
         let merged: Vec<u64> = vec![0, 1, 7, 8, 9];
 
         for i in merged {
+            let i = Node::from(i);
             let x = proj.find(i);
             if i == x {
-                assert!(graph.graph.contains_node(i));
+                assert!(graph.contains_node(i));
             } else {
-                assert!(!graph.graph.contains_node(i));
+                assert!(!graph.contains_node(i));
             }
         }
     }
+
+    #[test]
+    fn merge_vertices_into_keeps_vertex() {
+        let edges =
+            vec![(0, 1), (0, 2), (1, 3), (2, 3), (3, 4), (3, 5), (3, 0)];
+
+        let mut graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_directed_edges(edges).unwrap();
+        let mut proj = Projection::new_for_biedged_graph(&graph);
+
+        // Plain merging would keep 7 here, as it's the first argument
+        // of the union of two singletons
+        let kept = graph.merge_vertices_into(8.into(), 7.into(), &mut proj);
+        assert_eq!(kept, Some(8.into()));
+
+        // 8 now has the higher rank, but 9 is kept regardless
+        let kept = graph.merge_vertices_into(9.into(), 8.into(), &mut proj);
+        assert_eq!(kept, Some(9.into()));
+
+        assert!(!graph.contains_node(7.into()));
+        assert!(!graph.contains_node(8.into()));
+
+        for &n in [7, 8, 9].iter() {
+            assert_eq!(proj.find(n.into()), Node::new(9));
+        }
+
+        let mut edges = graph
+            .graph
+            .edges(9.into())
+            .map(|(_, b, w)| (b.id, w.black, w.gray))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+
+        // The edges between the merged vertices are gone, and the
+        // other edges of 7 have been moved to 9
+        assert_eq!(edges, vec![(0, 0, 1), (6, 1, 0), (10, 0, 1)]);
+
+        assert_eq!(
+            graph.merge_vertices_into(9.into(), 7.into(), &mut proj),
+            None
+        );
+    }
+
+    #[test]
+    fn union_into_many_vertices() {
+        let black = (0..300).map(|i| (2 * i, 2 * i + 1)).collect::<Vec<_>>();
+        let graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_edges(&black, &[]);
+        let mut proj = Projection::new_for_biedged_graph(&graph);
+
+        for n in 1..600 {
+            assert!(proj.union_into(Node::new(0), Node::new(n)));
+        }
+
+        // merging a set into a vertex that isn't its representative
+        // moves the representative
+        assert!(!proj.union_into(Node::new(599), Node::new(0)));
+
+        for n in 0..600 {
+            assert_eq!(proj.find(Node::new(n)), Node::new(599));
+        }
+    }
 }
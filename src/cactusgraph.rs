@@ -1,9 +1,12 @@
+use gfa::gfa::GFA;
 use log::{debug, trace};
 use petgraph::prelude::*;
 use rayon::prelude::*;
 
 use rustc_hash::{FxHashMap, FxHashSet};
 
+use std::collections::BTreeMap;
+
 use crate::{
     biedgedgraph::{BiedgedGraph, BiedgedWeight},
     netgraph::NetGraph,
@@ -11,9 +14,10 @@ use crate::{
         canonical_id, end_to_black_edge, opposite_vertex, Projection,
     },
     snarls::{
-        Biedged, Bridge, Cactus, Node, Snarl, SnarlMap, SnarlMapIter, SnarlType,
+        Biedged, Bridge, Cactus, Node, Snarl, SnarlMap, SnarlMapBuilder,
+        SnarlTree, SnarlType,
     },
-    ultrabubble::{BridgePair, ChainPair},
+    ultrabubble::{BridgePair, ChainPair, Ultrabubble},
 };
 
 #[cfg(feature = "progress_bars")]
@@ -107,6 +111,23 @@ impl<'a> CactusGraph<'a> {
     /// original.
     pub fn from_biedged_graph(
         biedged_graph: &'a BiedgedGraph<Biedged>,
+    ) -> Self {
+        Self::build(biedged_graph, None)
+    }
+
+    /// Like `from_biedged_graph`, but also records the graph in
+    /// `trace` before contracting the gray edges, after contracting
+    /// them, and after merging the 3-edge-connected components.
+    pub fn from_biedged_graph_traced(
+        biedged_graph: &'a BiedgedGraph<Biedged>,
+        trace: &mut PipelineTrace,
+    ) -> Self {
+        Self::build(biedged_graph, Some(trace))
+    }
+
+    fn build(
+        biedged_graph: &'a BiedgedGraph<Biedged>,
+        mut trace: Option<&mut PipelineTrace>,
     ) -> Self {
         debug!("  ~~~  building cactus graph  ~~~");
         debug!("cloning biedged graph");
@@ -129,21 +150,37 @@ impl<'a> CactusGraph<'a> {
         let mut projection = Projection::new_for_biedged_graph(&graph);
         trace!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
 
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.record("biedged", &graph);
+        }
+
         debug!("contracting gray edges");
         let t = std::time::Instant::now();
         Self::contract_all_gray_edges(&mut graph, &mut projection);
         debug!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
 
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.record("gray contraction", &graph);
+        }
+
         debug!("finding 3-edge-connected components");
         let t = std::time::Instant::now();
-        let components = Self::find_3_edge_connected_components(&graph);
+        let components = Self::find_3_edge_connected_components_nodes(&graph);
         debug!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
 
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.three_edge_components = components.len();
+        }
+
         debug!("merging 3-edge-connected components");
         let t = std::time::Instant::now();
         Self::merge_components(&mut graph, components, &mut projection);
         debug!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
 
+        if let Some(trace) = trace {
+            trace.record("component merge", &graph);
+        }
+
         graph.shrink_to_fit();
 
         let (node_count, node_cap) = graph.node_count_capacity();
@@ -209,8 +246,6 @@ impl<'a> CactusGraph<'a> {
             total_cap += cycle.capacity();
             for &(a, b) in cycle.iter() {
                 let a_inv = inv_proj.get(&a.id).unwrap();
-                let b_inv = inv_proj.get(&b.id).unwrap();
-
                 let b_set = inv_proj
                     .get(&b.id)
                     .unwrap()
@@ -303,6 +338,9 @@ impl<'a> CactusGraph<'a> {
         }
     }
 
+    /// Contract every gray edge in the graph. Parallel gray edges,
+    /// i.e. gray edges with a multiplicity greater than one, are all
+    /// contracted at once, as are gray self-loops.
     pub fn contract_all_gray_edges(
         biedged: &mut BiedgedGraph<Cactus>,
         projection: &mut Projection,
@@ -338,7 +376,7 @@ impl<'a> CactusGraph<'a> {
         let t = std::time::Instant::now();
         let gray_edges = biedged
             .gray_edges()
-            .map(|(a, b, _w)| (a, b))
+            .map(|(a, b, w)| (a, b, w.gray))
             .collect::<Vec<_>>();
         trace!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
         trace!("collected gray edges");
@@ -349,7 +387,7 @@ impl<'a> CactusGraph<'a> {
             gray_edges.capacity()
         );
 
-        for (from, to) in gray_edges {
+        for (from, to, _multiplicity) in gray_edges {
             let from_ = projection.find(from);
             let to_ = projection.find(to);
             let edge = biedged.graph.edge_weight(from_, to_).copied();
@@ -362,7 +400,7 @@ impl<'a> CactusGraph<'a> {
 
             #[cfg(feature = "progress_bars")]
             {
-                _p_bar.inc(1);
+                _p_bar.inc(_multiplicity as u64);
             }
         }
 
@@ -370,13 +408,20 @@ impl<'a> CactusGraph<'a> {
         {
             _p_bar.finish();
         }
+
+        debug_assert!(biedged.is_cactus_ready());
     }
 
+    /// Find the 3-edge-connected components of the graph, using its
+    /// black edges. The gray edges must already have been contracted;
+    /// see `BiedgedGraph::is_cactus_ready`.
     pub fn find_3_edge_connected_components(
         biedged: &BiedgedGraph<Cactus>,
     ) -> Vec<Vec<usize>> {
+        debug_assert!(biedged.is_cactus_ready());
+
         let edges = biedged.graph.all_edges().flat_map(|(a, b, w)| {
-            std::iter::repeat((a.id as usize, b.id as usize)).take(w.black)
+            std::iter::repeat_n((a.id as usize, b.id as usize), w.black)
         });
 
         let graph = three_edge_connected::Graph::from_edges(edges);
@@ -390,26 +435,128 @@ impl<'a> CactusGraph<'a> {
         // zero; even if the input biedged graph also does so, it's
         // better to make sure the node IDs are mapped backed to their
         // input IDs.
-        graph.invert_components(components)
+        let mut components = graph.invert_components(components);
+        sort_components(&mut components);
+        components
+    }
+
+    /// Find the 3-edge-connected components of each connected
+    /// component of the graph in parallel. The components found are
+    /// the same as those from `find_3_edge_connected_components`, in
+    /// the same order.
+    #[cfg(feature = "parallel")]
+    pub fn find_3_edge_connected_components_parallel(
+        biedged: &BiedgedGraph<Cactus>,
+    ) -> Vec<Vec<usize>> {
+        debug_assert!(biedged.is_cactus_ready());
+
+        let connected = biedged.connected_components();
+
+        let component_ix: FxHashMap<Node, usize> = connected
+            .iter()
+            .enumerate()
+            .flat_map(|(ix, comp)| comp.iter().map(move |&n| (n, ix)))
+            .collect();
+
+        let mut component_edges: Vec<Vec<(usize, usize)>> =
+            vec![Vec::new(); connected.len()];
+
+        for (a, b, w) in biedged.graph.all_edges() {
+            let edges = &mut component_edges[component_ix[&a]];
+            edges.extend(std::iter::repeat_n(
+                (a.id as usize, b.id as usize),
+                w.black,
+            ));
+        }
+
+        let mut components = component_edges
+            .into_par_iter()
+            .flat_map(|edges| {
+                let graph =
+                    three_edge_connected::Graph::from_edges(edges.into_iter());
+
+                let components =
+                    three_edge_connected::find_components(&graph.graph);
+
+                let components: Vec<_> =
+                    components.into_iter().filter(|c| c.len() > 1).collect();
+
+                graph.invert_components(components)
+            })
+            .collect::<Vec<_>>();
+
+        sort_components(&mut components);
+        components
+    }
+
+    /// Like `find_3_edge_connected_components`, but with the vertices
+    /// of the components as `Node`s rather than raw indices.
+    pub fn find_3_edge_connected_components_nodes(
+        biedged: &BiedgedGraph<Cactus>,
+    ) -> Vec<Vec<Node>> {
+        Self::find_3_edge_connected_components(biedged)
+            .into_iter()
+            .map(|comp| {
+                comp.into_iter().map(|n| Node::from(n as u64)).collect()
+            })
+            .collect()
+    }
+
+    /// Returns the first vertex found that's in more than one of the
+    /// components, or None if the components are disjoint.
+    pub fn shared_component_vertex(components: &[Vec<Node>]) -> Option<Node> {
+        let mut owners: FxHashMap<Node, usize> = FxHashMap::default();
+
+        for (ix, comp) in components.iter().enumerate() {
+            for &n in comp.iter() {
+                if *owners.entry(n).or_insert(ix) != ix {
+                    return Some(n);
+                }
+            }
+        }
+
+        None
     }
 
+    /// Merge the vertices of each component into a single vertex.
+    /// The vertex with the smallest ID in each component is the one
+    /// that's kept, and that the rest of the component projects to.
+    /// The gray edges must already have been contracted.
+    ///
+    /// Panics if the components aren't disjoint, as merging a vertex
+    /// into two components would corrupt the projection.
     pub fn merge_components(
         biedged: &mut BiedgedGraph<Cactus>,
-        components: Vec<Vec<usize>>,
+        components: Vec<Vec<Node>>,
         projection: &mut Projection,
     ) {
+        debug_assert!(biedged.is_cactus_ready());
+
+        if let Some(shared) = Self::shared_component_vertex(&components) {
+            panic!(
+                "vertex {} is in more than one 3-edge-connected component",
+                shared
+            );
+        }
+
         for comp in components {
-            let mut iter = comp.into_iter();
-            let head = Node::from(iter.next().unwrap() as u64);
-            for other in iter {
-                let other = Node::from(other as u64);
-                if biedged.graph.contains_node(head)
+            let head = match comp.iter().min() {
+                Some(&head) => head,
+                None => continue,
+            };
+
+            for other in comp {
+                if other != head
+                    && biedged.graph.contains_node(head)
                     && biedged.graph.contains_node(other)
                 {
                     if biedged.graph.contains_edge(head, other) {
+                        // making `head` the representative first means
+                        // the contraction keeps it
+                        projection.union_into(head, other);
                         biedged.contract_edge(head, other, projection);
                     } else {
-                        biedged.merge_vertices(head, other, projection);
+                        biedged.merge_vertices_into(head, other, projection);
                     }
                 }
             }
@@ -443,10 +590,10 @@ impl<'a> CactusGraph<'a> {
                                 }
                             } else if !visited.contains(&adj) {
                                 if weight.black == 2 {
-                                    cycles.push(vec![
+                                    cycles.push(canonicalize_cycle(&[
                                         (current, adj),
                                         (adj, current),
-                                    ]);
+                                    ]));
                                 }
                                 stack.push(adj);
                                 parents.insert(adj, current);
@@ -471,7 +618,7 @@ impl<'a> CactusGraph<'a> {
             }
 
             cycle.push((start, end));
-            cycles.push(cycle);
+            cycles.push(canonicalize_cycle(&cycle));
         }
 
         cycles
@@ -492,12 +639,11 @@ impl<'a> CactusGraph<'a> {
     #[inline]
     fn black_edge_cycle(&self, x: Node) -> Option<&Vec<usize>> {
         let edge = self.black_edge_projection(x);
-        let cycles = self
-            .cycle_map
-            .get(&(Node::from(edge.0), Node::from(edge.1)))?;
-        Some(&cycles)
+        let cycles = self.cycle_map.get(&edge)?;
+        Some(cycles)
     }
 
+    #[allow(dead_code)]
     #[inline]
     fn black_edge_cycle_rank(&self, x: Node) -> Option<usize> {
         let canonical = canonical_id(x.id);
@@ -506,6 +652,214 @@ impl<'a> CactusGraph<'a> {
     }
 }
 
+/// Returns the canonical representation of a cycle, given as a list
+/// of edges where each edge starts at the vertex the previous one
+/// ended at. The canonical cycle starts at its smallest vertex, and
+/// goes in whichever direction gives the lexicographically smaller
+/// list of edges.
+pub fn canonicalize_cycle(cycle: &[(Node, Node)]) -> Vec<(Node, Node)> {
+    let start = match cycle.iter().enumerate().min_by_key(|(_, (a, _))| *a) {
+        Some((ix, _)) => ix,
+        None => return Vec::new(),
+    };
+
+    let forward = cycle[start..]
+        .iter()
+        .chain(cycle[..start].iter())
+        .copied()
+        .collect::<Vec<_>>();
+
+    // Walking the cycle backward from the smallest vertex means
+    // starting with the edge that ends at it
+    let backward = forward
+        .iter()
+        .rev()
+        .map(|&(a, b)| (b, a))
+        .collect::<Vec<_>>();
+
+    forward.min(backward)
+}
+
+/// Rotates a cycle, given as the list of vertices along it, so that
+/// it starts at `start`, keeping its direction. Returns None if
+/// `start` isn't on the cycle.
+pub fn rotate_cycle(cycle: &[Node], start: Node) -> Option<Vec<Node>> {
+    let ix = cycle.iter().position(|&n| n == start)?;
+    let mut rotated = cycle.to_vec();
+    rotated.rotate_left(ix);
+    Some(rotated)
+}
+
+/// Returns the edges between consecutive vertices of a cycle, given
+/// as the list of vertices along it, including the edge from the
+/// last vertex back to the first, along with their weights in
+/// `biedged`. A cycle with a single vertex is a self-loop. Pairs of
+/// vertices that aren't adjacent in `biedged` are skipped.
+pub fn cycle_edges<G: Copy>(
+    cycle: &[Node],
+    biedged: &BiedgedGraph<G>,
+) -> Vec<(Node, Node, BiedgedWeight)> {
+    let len = cycle.len();
+
+    (0..len)
+        .filter_map(|ix| {
+            let from = cycle[ix];
+            let to = cycle[(ix + 1) % len];
+            let weight = biedged.edge_weight(from, to)?;
+            Some((from, to, weight))
+        })
+        .collect()
+}
+
+/// Given a cycle in a biedged graph as the list of vertices along
+/// it, starting with one end of a black edge and alternating between
+/// black edges and the vertices they meet at, return the chain pairs
+/// the cycle produces. Each chain pair consists of the end of one
+/// black edge and the start of the next, wrapping around at the end
+/// of the cycle.
+pub fn cycle_chain_pairs(cycle: &[Node]) -> Vec<Snarl<()>> {
+    let len = cycle.len();
+
+    if len < 2 {
+        return Vec::new();
+    }
+
+    (1..len)
+        .step_by(2)
+        .map(|ix| Snarl::chain_pair(cycle[ix], cycle[(ix + 1) % len]))
+        .collect()
+}
+
+/// Decompose a cactus graph into its chains, the maximal sequences
+/// of simple cycles that are connected to one another at shared
+/// vertices. Each chain is the list of its cycles, in breadth-first
+/// order starting from the cycle with the smallest vertex, and each
+/// cycle is the list of vertices along it. Black self-loops are not
+/// included as cycles.
+pub fn chain_decomposition(
+    cactus: &BiedgedGraph<Cactus>,
+) -> Vec<Vec<Vec<Node>>> {
+    let mut cycles = CactusGraph::find_cycles(cactus)
+        .into_iter()
+        .filter(|cycle| cycle.len() > 1)
+        .map(|cycle| cycle.into_iter().map(|(a, _)| a).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    cycles.sort();
+
+    let mut vertex_cycles: FxHashMap<Node, Vec<usize>> = FxHashMap::default();
+    for (ix, cycle) in cycles.iter().enumerate() {
+        for &node in cycle.iter() {
+            vertex_cycles.entry(node).or_default().push(ix);
+        }
+    }
+
+    let mut visited = vec![false; cycles.len()];
+    let mut chains = Vec::new();
+
+    for start in 0..cycles.len() {
+        if visited[start] {
+            continue;
+        }
+
+        let mut chain = Vec::new();
+        let mut queue = std::collections::VecDeque::new();
+
+        visited[start] = true;
+        queue.push_back(start);
+
+        while let Some(ix) = queue.pop_front() {
+            chain.push(ix);
+            for node in cycles[ix].iter() {
+                for &other in vertex_cycles[node].iter() {
+                    if !visited[other] {
+                        visited[other] = true;
+                        queue.push_back(other);
+                    }
+                }
+            }
+        }
+
+        chains.push(chain);
+    }
+
+    chains
+        .into_iter()
+        .map(|chain| chain.into_iter().map(|ix| cycles[ix].clone()).collect())
+        .collect()
+}
+
+const DOT_PALETTE: [&str; 8] = [
+    "red", "blue", "green", "orange", "purple", "cyan", "magenta", "brown",
+];
+
+/// Render a cactus graph in the GraphViz DOT format, with the edges
+/// of each of the provided cycles, given as lists of vertices as
+/// produced by `chain_decomposition`, drawn in a color picked by the
+/// index of the cycle. Edges that aren't part of any cycle, such as
+/// bridges, are drawn in black, and any remaining gray edges dashed.
+pub fn cactus_to_dot(
+    cactus: &BiedgedGraph<Cactus>,
+    cycles: &[Vec<Node>],
+) -> String {
+    use std::fmt::Write;
+
+    let mut cycle_edges: FxHashMap<(Node, Node), Vec<usize>> =
+        FxHashMap::default();
+
+    for (ix, cycle) in cycles.iter().enumerate() {
+        let len = cycle.len();
+        for i in 0..len {
+            let (a, b) = (cycle[i], cycle[(i + 1) % len]);
+            cycle_edges
+                .entry((a.min(b), a.max(b)))
+                .or_default()
+                .push(ix);
+        }
+    }
+
+    let mut nodes = cactus.graph.nodes().collect::<Vec<_>>();
+    nodes.sort_unstable();
+
+    let mut edges = cactus
+        .graph
+        .all_edges()
+        .map(|(a, b, w)| (a.min(b), a.max(b), *w))
+        .collect::<Vec<_>>();
+    edges.sort_unstable_by_key(|&(a, b, _)| (a, b));
+
+    let mut dot = String::from("graph cactus {\n");
+
+    for node in nodes {
+        writeln!(dot, "    {};", node.id).unwrap();
+    }
+
+    for (a, b, w) in edges {
+        let mut colors = cycle_edges
+            .get(&(a, b))
+            .into_iter()
+            .flatten()
+            .map(|&ix| DOT_PALETTE[ix % DOT_PALETTE.len()]);
+
+        for _ in 0..w.black {
+            let color = colors.next().unwrap_or("black");
+            writeln!(dot, "    {} -- {} [color={}];", a.id, b.id, color)
+                .unwrap();
+        }
+
+        for _ in 0..w.gray {
+            writeln!(
+                dot,
+                "    {} -- {} [color=gray, style=dashed];",
+                a.id, b.id
+            )
+            .unwrap();
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
 /// A cactus tree derived from a cactus graph. Like the CactusGraph
 /// struct, this clones the underlying graph before mutating it into a
 /// cactus tree, and keeps a reference both to the original biedged
@@ -650,7 +1004,9 @@ impl<'a> CactusTree<'a> {
             let chain_vx = biedged.add_chain_vertex();
 
             for &(from, to) in cycle {
-                cycle_chain_map.insert((from, to), chain_vx);
+                // keyed by the sorted edge, as black_edge_chain_vertex
+                // looks it up
+                cycle_chain_map.insert((from.min(to), from.max(to)), chain_vx);
                 biedged.add_edge(to, chain_vx, BiedgedWeight::black(1));
                 biedged.remove_one_black_edge(from, to);
             }
@@ -670,6 +1026,42 @@ impl<'a> CactusTree<'a> {
         Some(*chain_vx)
     }
 
+    /// Returns the chain vertices of the cactus tree, one for each
+    /// cycle in the cactus graph, in ascending order.
+    pub fn chains(&self) -> Vec<Node> {
+        let mut chains =
+            self.chain_vertices.iter().copied().collect::<Vec<_>>();
+        chains.sort_unstable();
+        chains
+    }
+
+    /// Returns the net vertices of the cactus tree, i.e. the vertices
+    /// of the cactus graph, in ascending order.
+    pub fn net_vertices(&self) -> Vec<Node> {
+        let mut nets = self.graph.net_vertices().collect::<Vec<_>>();
+        nets.sort_unstable();
+        nets
+    }
+
+    /// Returns the chain vertices adjacent to the net vertex `net`,
+    /// i.e. the chains of the cycles `net` is part of, in ascending
+    /// order. Returns an empty vector if `net` isn't on any cycle.
+    pub fn chain_of(&self, net: Node) -> Vec<Node> {
+        if !self.graph.contains_node(net) {
+            return Vec::new();
+        }
+
+        let mut chains = self
+            .graph
+            .graph
+            .neighbors(net)
+            .filter(|n| self.chain_vertices.contains(n))
+            .collect::<Vec<_>>();
+        chains.sort_unstable();
+        chains.dedup();
+        chains
+    }
+
     /// Find the chain pairs using the chain vertices in the cactus
     /// tree, and return them as a set of snarls.
     pub fn find_chain_pairs(&self) -> FxHashSet<ChainPair> {
@@ -830,7 +1222,7 @@ impl<'a> CactusTree<'a> {
             stack.push(p_x);
 
             while let Some(current) = stack.pop() {
-                if !(current != p_y) && !visited.contains(&current) {
+                if current == p_y && !visited.contains(&current) {
                     visited.insert(current);
 
                     let current_net_vertex = cactus_tree.is_net_vertex(current);
@@ -874,7 +1266,7 @@ impl<'a> CactusTree<'a> {
     }
 
     fn net_graph_black_edge_walk(
-        vertices: &FxHashSet<Node>,
+        _vertices: &FxHashSet<Node>,
         biedged: &BiedgedGraph<Biedged>,
         x: Node,
         y: Node,
@@ -1132,7 +1524,7 @@ impl<'a> CactusTree<'a> {
 
         for &cx in chain_vertices.iter() {
             let net_neighbors = self.base_graph().neighbors(cx).filter(|n| {
-                !path_vertices.contains(&n) && !chain_vertices.contains(&n)
+                !path_vertices.contains(n) && !chain_vertices.contains(n)
             });
 
             for nx in net_neighbors {
@@ -1260,8 +1652,15 @@ impl<'a> BridgeForest<'a> {
                 let from = projection.find(from);
                 let to = projection.find(to);
 
+                // keep the smaller vertex, so that each contracted
+                // cycle is named by its smallest vertex no matter the
+                // order the cycles and their edges are merged in
                 if from != to {
-                    biedged.merge_vertices(from, to, projection);
+                    biedged.merge_vertices_into(
+                        from.min(to),
+                        from.max(to),
+                        projection,
+                    );
                 }
             }
 
@@ -1271,6 +1670,11 @@ impl<'a> BridgeForest<'a> {
             }
         }
 
+        // merging only drops the self-loops of the removed vertex, so
+        // whichever vertex of a cycle was kept would otherwise hold on
+        // to its own; none of them are bridges
+        biedged.remove_self_loops();
+
         #[cfg(feature = "progress_bars")]
         {
             _p_bar.finish();
@@ -1355,6 +1759,83 @@ impl<'a> BridgeForest<'a> {
         bridge_pairs
     }
 
+    /// Returns the edges of the bridge forest, each as a sorted pair
+    /// of vertices, in ascending order. Self-loops left over from
+    /// contracting the cycles aren't included, so these are exactly
+    /// the bridges of the cactus graph.
+    pub fn tree_edges(&self) -> Vec<(Node, Node)> {
+        let mut edges = self
+            .graph
+            .graph
+            .all_edges()
+            .filter(|&(x, y, _)| x != y)
+            .map(|(x, y, _)| (x.min(y), x.max(y)))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+    }
+
+    /// Returns the leaves of the bridge forest, i.e. the vertices
+    /// with exactly one neighbor other than themselves, in ascending
+    /// order.
+    pub fn leaves(&self) -> Vec<Node> {
+        let graph = &self.graph.graph;
+        let mut leaves = graph
+            .nodes()
+            .filter(|&n| graph.neighbors(n).filter(|&m| m != n).count() == 1)
+            .collect::<Vec<_>>();
+        leaves.sort_unstable();
+        leaves
+    }
+
+    /// Returns the path through the bridge forest between the
+    /// vertices that `a` and `b` project to, including both ends, or
+    /// None if they're in different trees of the forest.
+    pub fn path_between(&self, a: Node, b: Node) -> Option<Vec<Node>> {
+        use std::collections::{hash_map::Entry, VecDeque};
+
+        let graph = &self.graph.graph;
+
+        let a = self.projected_node(a);
+        let b = self.projected_node(b);
+
+        if !graph.contains_node(a) || !graph.contains_node(b) {
+            return None;
+        }
+
+        let mut parents: FxHashMap<Node, Node> = FxHashMap::default();
+        let mut queue: VecDeque<Node> = VecDeque::new();
+
+        parents.insert(a, a);
+        queue.push_back(a);
+
+        while let Some(current) = queue.pop_front() {
+            if current == b {
+                break;
+            }
+
+            for next in graph.neighbors(current) {
+                if let Entry::Vacant(entry) = parents.entry(next) {
+                    entry.insert(current);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut path = vec![b];
+        let mut current = b;
+
+        while current != a {
+            current = *parents.get(&current)?;
+            path.push(current);
+        }
+
+        path.reverse();
+        Some(path)
+    }
+
+    /// Returns the black edges of the original graph that are bridges,
+    /// by their left-hand vertices, sorted and without duplicates.
     pub fn black_bridge_edges(&self) -> Vec<Node> {
         let mut res = Vec::new();
 
@@ -1366,7 +1847,7 @@ impl<'a> BridgeForest<'a> {
             let y_inv: FxHashSet<Node> = inv_map
                 .get(&y.id)
                 .unwrap()
-                .into_iter()
+                .iter()
                 .map(|&n| Node::new(n))
                 .collect::<FxHashSet<_>>();
 
@@ -1379,9 +1860,15 @@ impl<'a> BridgeForest<'a> {
             }
         }
 
+        res.sort_unstable();
+        res.dedup();
+
         res
     }
 
+    /// Mark the black bridge edges contained in each snarl in the map,
+    /// by walking the bridge forest out from each black bridge edge in
+    /// turn, in order.
     pub fn snarl_family(&self, snarl_map: &mut SnarlMap) {
         use std::collections::VecDeque;
 
@@ -1393,6 +1880,8 @@ impl<'a> BridgeForest<'a> {
 
         let mut snarls: Vec<Snarl<()>> = Vec::new();
 
+        let mut neighbors: Vec<Node> = Vec::new();
+
         debug!("iterating {} black bridge edges", black_bridge_edges.len());
 
         let _p_bar;
@@ -1479,13 +1968,19 @@ impl<'a> BridgeForest<'a> {
                     }
                 }
 
-                let neighbors = self.graph.graph.neighbors(node);
-
-                for other in neighbors {
-                    if !visited.contains(&other) {
-                        queue.push_back(other);
-                    }
-                }
+                // the marks depend on the order the walk reaches the
+                // vertices in, so don't leave it to the graph's edge
+                // order
+                neighbors.clear();
+                neighbors.extend(
+                    self.graph
+                        .graph
+                        .neighbors(node)
+                        .filter(|other| !visited.contains(other)),
+                );
+                neighbors.sort_unstable();
+
+                queue.extend(neighbors.iter().copied());
             }
 
             #[cfg(feature = "progress_bars")]
@@ -1721,7 +2216,7 @@ pub fn bridge_pair_ultrabubbles(
     bridge_pair_ultrabubbles.par_extend(label_iter.filter_map(
         |(&(x, y), path)| {
             let contained_chain_pairs = cactus_tree.is_bridge_pair_ultrabubble(
-                &chain_edge_labels,
+                chain_edge_labels,
                 x,
                 y,
                 path,
@@ -1787,11 +2282,11 @@ pub fn find_ultrabubbles(
     );
     debug!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
 
-    let chain_edges_map = chain_edges(&chain_pairs, &cactus_tree);
+    let chain_edges_map = chain_edges(&chain_pairs, cactus_tree);
 
     chain_ultrabubbles
         .into_iter()
-        .chain(bridge_ultrabubbles.into_iter())
+        .chain(bridge_ultrabubbles)
         .map(|(key, cont)| {
             (
                 (key.0.id, key.1.id),
@@ -1806,86 +2301,429 @@ pub fn find_ultrabubbles(
         .collect()
 }
 
-pub fn build_snarl_family(
-    cactus_tree: &CactusTree<'_>,
-    bridge_forest: &BridgeForest<'_>,
-) -> SnarlMap {
-    debug!("Finding chain pairs");
-    let t = std::time::Instant::now();
+/// Find the ultrabubbles of a GFA graph, running the entire pipeline
+/// from constructing the biedged graph to labeling the chain and
+/// bridge pairs. The boundaries are reported as oriented segments of
+/// the input graph, sorted.
+pub fn ultrabubbles(gfa: &GFA<usize, ()>) -> Vec<Ultrabubble> {
+    let biedged = BiedgedGraph::from_gfa(gfa);
+    let cactus_graph = CactusGraph::from_biedged_graph(&biedged);
+    let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+    let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
     let chain_pairs = cactus_tree.find_chain_pairs();
+    let bridge_pairs = bridge_forest.find_bridge_pairs();
+
+    let mut chain_edge_labels =
+        chain_pair_ultrabubble_labels(&cactus_tree, &chain_pairs);
+
+    let chain_ultrabubbles = chain_pair_contained_ultrabubbles(
+        &cactus_tree,
+        &chain_pairs,
+        &mut chain_edge_labels,
+    );
+
+    let bridge_ultrabubbles = bridge_pair_ultrabubbles(
+        &cactus_tree,
+        &bridge_pairs,
+        &chain_edge_labels,
+    );
+
+    let chains = chain_ultrabubbles.keys().map(|&(x, y)| {
+        Ultrabubble::from_boundaries(x, y, SnarlType::ChainPair)
+    });
+
+    let bridges = bridge_ultrabubbles.keys().map(|&(x, y)| {
+        Ultrabubble::from_boundaries(x, y, SnarlType::BridgePair)
+    });
+
+    let mut result = chains.chain(bridges).collect::<Vec<_>>();
+    result.sort();
+    result
+}
+
+/// Sort the vertices of each 3-edge-connected component, and the
+/// components by their smallest vertex, so that the output doesn't
+/// depend on the order the components were found in.
+fn sort_components(components: &mut [Vec<usize>]) {
+    for comp in components.iter_mut() {
+        comp.sort_unstable();
+    }
+    // the components are disjoint, so this compares their first,
+    // and smallest, vertices
+    components.sort_unstable();
+}
+
+/// Construct the cactus graph of a biedged graph, by contracting its
+/// gray edges and merging its 3-edge-connected components, without
+/// modifying the provided graph. Returns the cactus graph along with
+/// the map from each vertex in the biedged graph to the vertex it was
+/// projected to in the cactus graph.
+pub fn build_cactus(
+    biedged: &BiedgedGraph<Biedged>,
+) -> (BiedgedGraph<Cactus>, BTreeMap<u64, u64>) {
+    let mut graph = biedged.clone().set_graph_type::<Cactus>();
+    let mut projection = Projection::new_for_biedged_graph(&graph);
+
+    debug!("contracting gray edges");
+    let t = std::time::Instant::now();
+    CactusGraph::contract_all_gray_edges(&mut graph, &mut projection);
     debug!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
-    debug!("Found {} chain pairs", chain_pairs.len());
 
-    debug!("Finding bridge pairs");
+    debug!("finding 3-edge-connected components");
     let t = std::time::Instant::now();
-    let bridge_pairs = bridge_forest.find_bridge_pairs();
+    let components =
+        CactusGraph::find_3_edge_connected_components_nodes(&graph);
     debug!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
-    debug!("Found {} bridge pairs", bridge_pairs.len());
 
-    let mut snarl_map = SnarlMap::default();
-    debug!(
-        "Adding {} + {} = {} snarls",
-        bridge_pairs.len(),
-        chain_pairs.len(),
-        bridge_pairs.len() + chain_pairs.len()
-    );
+    debug!("merging 3-edge-connected components");
+    let t = std::time::Instant::now();
+    CactusGraph::merge_components(&mut graph, components, &mut projection);
+    debug!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
 
-    for &bp in bridge_pairs.iter() {
-        trace!("Bridge pair  ({}, {})", bp.x, bp.y);
-        snarl_map
-            .insert(Snarl::<()>::bridge_pair(Node::new(bp.x), Node::new(bp.y)));
+    (graph, projection.projection_map())
+}
+
+/// Construct the quotient of a graph by a projection map, as
+/// returned by `build_cactus`, by replacing each vertex with its
+/// projection and adding up the weights of edges that end up between
+/// the same vertices. Vertices missing from the map are kept as they
+/// are. Gray edges within a 3-edge-connected component become gray
+/// self-loops, so the black edges of the result form the cactus
+/// graph.
+pub fn net_graph<G: Copy>(
+    biedged: &BiedgedGraph<G>,
+    proj_map: &BTreeMap<u64, u64>,
+) -> BiedgedGraph<G> {
+    let project =
+        |n: Node| -> Node { proj_map.get(&n.id).map_or(n, |&p| Node::new(p)) };
+
+    let mut graph: BiedgedGraph<G> = BiedgedGraph {
+        max_net_vertex: biedged.max_net_vertex,
+        max_chain_vertex: biedged.max_chain_vertex,
+        ..Default::default()
+    };
+
+    for node in biedged.graph.nodes() {
+        graph.graph.add_node(project(node));
     }
 
-    for &cp in chain_pairs.iter() {
-        trace!("Chain pair   ({}, {})", cp.x, cp.y);
-        snarl_map
-            .insert(Snarl::<()>::chain_pair(Node::new(cp.x), Node::new(cp.y)));
+    for (a, b, &w) in biedged.graph.all_edges() {
+        graph.add_edge(project(a), project(b), w);
     }
 
-    debug!("filtering compatible snarl family");
-    bridge_forest.snarl_family(&mut snarl_map);
+    graph
+}
 
-    snarl_map
+/// Statistics describing the decomposition of a graph into its
+/// cactus graph and snarls, as produced by `decomposition_summary`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DecompositionSummary {
+    /// Vertices in the biedged graph
+    pub nodes: usize,
+    /// Black edges in the biedged graph
+    pub black_edges: usize,
+    /// Gray edges in the biedged graph
+    pub gray_edges: usize,
+    /// 3-edge-connected components with more than one vertex, after
+    /// contracting the gray edges
+    pub three_edge_components: usize,
+    /// Simple cycles in the cactus graph, including black self-loops
+    pub cycles: usize,
+    /// Chain pairs in the snarl family
+    pub chain_pairs: usize,
+    /// Bridge pairs in the snarl family
+    pub bridge_pairs: usize,
+    /// Greatest nesting depth in the snarl tree
+    pub max_depth: usize,
 }
 
-/// Inverses the vertex projection of the provided ultrabubbles to the
-/// node ID space of the graph used to construct the original biedged
-/// graph.
-pub fn inverse_map_ultrabubbles(
-    ultrabubbles: FxHashMap<(u64, u64), Vec<(u64, u64)>>,
-) -> FxHashMap<(u64, u64), Vec<(u64, u64)>> {
-    ultrabubbles
-        .into_iter()
-        .map(|((x, y), contained)| {
-            use crate::projection::id_from_black_edge;
-            let x = id_from_black_edge(x);
-            let y = id_from_black_edge(y);
-            let contained = contained
-                .into_iter()
-                .map(|(a, b)| (id_from_black_edge(a), id_from_black_edge(b)))
-                .collect();
-            ((x, y), contained)
-        })
-        .collect()
+/// Run the entire pipeline on a GFA graph, from constructing the
+/// biedged graph to building the snarl tree, and summarize the
+/// result.
+pub fn decomposition_summary(gfa: &GFA<usize, ()>) -> DecompositionSummary {
+    let biedged = BiedgedGraph::from_gfa(gfa);
+
+    let mut trace = PipelineTrace::default();
+    let cactus_graph =
+        CactusGraph::from_biedged_graph_traced(&biedged, &mut trace);
+    let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+    let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+    let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+    let chain_pairs = snarl_map
+        .snarls
+        .values()
+        .filter(|s| s.is_chain_pair())
+        .count();
+    let bridge_pairs = snarl_map
+        .snarls
+        .values()
+        .filter(|s| s.is_bridge_pair())
+        .count();
+
+    let snarl_tree = SnarlTree::from_snarl_map(snarl_map);
+
+    DecompositionSummary {
+        nodes: biedged.graph.node_count(),
+        black_edges: biedged.black_edge_count(),
+        gray_edges: biedged.gray_edge_count(),
+        three_edge_components: trace.three_edge_components,
+        cycles: cactus_graph.cycles.len(),
+        chain_pairs,
+        bridge_pairs,
+        max_depth: snarl_tree.max_depth(),
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    fn graph_from_paper() -> BiedgedGraph<Biedged> {
-        let edges = vec![
-            (0, 1),
-            (0, 2),
-            (1, 3),
-            (2, 3),
-            (3, 4),
-            (3, 5),
-            (4, 6),
-            (5, 6),
-            (5, 7),
-            (6, 10),
-            (6, 11),
-            (7, 8),
+/// Snapshots of the graph taken between the steps of constructing
+/// the cactus graph, each labeled with the name of the step that
+/// produced it. The first stage is the unmodified biedged graph.
+#[derive(Clone, Default)]
+pub struct PipelineTrace {
+    pub stages: Vec<(&'static str, BiedgedGraph<Cactus>)>,
+    /// The number of 3-edge-connected components with more than one
+    /// vertex that were merged
+    pub three_edge_components: usize,
+}
+
+impl PipelineTrace {
+    fn record(&mut self, name: &'static str, graph: &BiedgedGraph<Cactus>) {
+        self.stages.push((name, graph.clone()));
+    }
+
+    /// The number of stages that were recorded.
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// The number of distinct edges in the graph at each stage.
+    pub fn edge_counts(&self) -> Vec<usize> {
+        self.stages
+            .iter()
+            .map(|(_, graph)| graph.graph.edge_count())
+            .collect()
+    }
+}
+
+/// Find the snarls of a GFA graph, like running `build_snarl_family`
+/// on the cactus tree and bridge forest of the graph, while also
+/// recording the state of the graph after each of the steps of
+/// constructing the cactus graph: contracting the gray edges, and
+/// merging the 3-edge-connected components.
+pub fn snarls_from_gfa_traced(
+    gfa: &GFA<usize, ()>,
+) -> (SnarlMap, PipelineTrace) {
+    let biedged = BiedgedGraph::from_gfa(gfa);
+
+    let mut trace = PipelineTrace::default();
+
+    let cactus_graph =
+        CactusGraph::from_biedged_graph_traced(&biedged, &mut trace);
+    let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+    let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+    let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+    (snarl_map, trace)
+}
+
+/// Find the snarls of a GFA graph, passing each one to `sink` as
+/// it's found rather than collecting them in a `SnarlMap`. The bridge
+/// pairs are emitted first, followed by the chain pairs that don't
+/// have the same boundaries as a bridge pair, which are the same
+/// snarls `build_snarl_family` produces with the default builder.
+/// As there's no snarl map, the contained black edges of each snarl
+/// are not found.
+///
+/// Only the snarl map is avoided: memory use is not bounded by the
+/// number of snarls held at once. The full biedged and cactus graphs,
+/// the bridge forest, and the boundaries of every bridge pair are
+/// built before the first snarl is emitted, and the cactus tree is
+/// built while the boundaries are still held, so the peak memory is
+/// close to that of `build_snarl_family` on the same graph.
+pub fn snarls_from_gfa_streaming<F>(gfa: &GFA<usize, ()>, mut sink: F)
+where
+    F: FnMut(Snarl<()>),
+{
+    let biedged = BiedgedGraph::from_gfa(gfa);
+    let cactus_graph = CactusGraph::from_biedged_graph(&biedged);
+
+    let mut bridge_boundaries: FxHashSet<(Node, Node)> = FxHashSet::default();
+
+    {
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+        for bp in bridge_forest.find_bridge_pairs() {
+            let snarl =
+                Snarl::<()>::bridge_pair(Node::new(bp.x), Node::new(bp.y));
+            if bridge_boundaries.insert((snarl.left(), snarl.right())) {
+                sink(snarl);
+            }
+        }
+    }
+
+    let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+
+    for cp in cactus_tree.find_chain_pairs() {
+        let snarl = Snarl::<()>::chain_pair(Node::new(cp.x), Node::new(cp.y));
+        if !bridge_boundaries.contains(&(snarl.left(), snarl.right())) {
+            sink(snarl);
+        }
+    }
+}
+
+/// Find the snarls of the subgraph induced by a set of segments,
+/// given by their IDs in the GFA the biedged graph was built from.
+/// The subgraph consists of the black edges of those segments, and
+/// the gray edges between them.
+///
+/// The snarls are only those of the subgraph: any snarl of the full
+/// graph that crosses the boundary of the region is omitted, and new
+/// snarls may appear where the region was cut from the rest of the
+/// graph.
+pub fn snarls_in_subgraph(
+    biedged: &BiedgedGraph<Biedged>,
+    nodes: &FxHashSet<u64>,
+) -> SnarlMap {
+    let mut subgraph: BiedgedGraph<Biedged> = BiedgedGraph::default();
+
+    let in_region = |n: Node| nodes.contains(&n.to_gfa_id());
+
+    for (a, b, w) in biedged.graph.all_edges() {
+        if in_region(a) && in_region(b) {
+            subgraph.add_edge(a, b, *w);
+        }
+    }
+
+    for &id in nodes.iter() {
+        let (left, right) = Node::from_gfa_id(id);
+        if biedged.graph.contains_node(left) {
+            subgraph.graph.add_node(left);
+        }
+        if biedged.graph.contains_node(right) {
+            subgraph.graph.add_node(right);
+        }
+    }
+
+    subgraph.max_net_vertex = biedged.max_net_vertex;
+    subgraph.max_chain_vertex = biedged.max_chain_vertex;
+
+    let cactus_graph = CactusGraph::from_biedged_graph(&subgraph);
+    let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+    let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+    build_snarl_family(&cactus_tree, &bridge_forest)
+}
+
+/// Assemble the snarl map of a graph from its cactus tree and bridge
+/// forest: finds the chain pairs and the bridge pairs, inserts them,
+/// and filters them down to the compatible snarl family, marking the
+/// bridges contained in each bridge pair.
+///
+/// This takes the cactus tree and bridge forest rather than their bare
+/// graphs and a projection map, as finding the chain pairs needs the
+/// cycles of the cactus graph, and the bridge forest has a projection
+/// of its own.
+pub fn build_snarl_family(
+    cactus_tree: &CactusTree<'_>,
+    bridge_forest: &BridgeForest<'_>,
+) -> SnarlMap {
+    build_snarl_family_with(cactus_tree, bridge_forest, SnarlMapBuilder::new())
+}
+
+/// Like `build_snarl_family`, but constructing the snarl map using
+/// the provided builder, and finalizing it once the snarl family has
+/// been found.
+pub fn build_snarl_family_with(
+    cactus_tree: &CactusTree<'_>,
+    bridge_forest: &BridgeForest<'_>,
+    builder: SnarlMapBuilder,
+) -> SnarlMap {
+    debug!("Finding chain pairs");
+    let t = std::time::Instant::now();
+    let chain_pairs = cactus_tree.find_chain_pairs();
+    debug!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
+    debug!("Found {} chain pairs", chain_pairs.len());
+
+    debug!("Finding bridge pairs");
+    let t = std::time::Instant::now();
+    let bridge_pairs = bridge_forest.find_bridge_pairs();
+    debug!("  took {:.3} ms", t.elapsed().as_secs_f64() * 1000.0);
+    debug!("Found {} bridge pairs", bridge_pairs.len());
+
+    let mut snarl_map = builder.build();
+    debug!(
+        "Adding {} + {} = {} snarls",
+        bridge_pairs.len(),
+        chain_pairs.len(),
+        bridge_pairs.len() + chain_pairs.len()
+    );
+
+    for &bp in bridge_pairs.iter() {
+        trace!("Bridge pair  ({}, {})", bp.x, bp.y);
+        snarl_map
+            .insert(Snarl::<()>::bridge_pair(Node::new(bp.x), Node::new(bp.y)));
+    }
+
+    for &cp in chain_pairs.iter() {
+        trace!("Chain pair   ({}, {})", cp.x, cp.y);
+        snarl_map
+            .insert(Snarl::<()>::chain_pair(Node::new(cp.x), Node::new(cp.y)));
+    }
+
+    debug!("filtering compatible snarl family");
+    bridge_forest.snarl_family(&mut snarl_map);
+
+    let removed = snarl_map.finalize();
+    debug!("Removed {} snarls below the minimum size", removed);
+
+    snarl_map
+}
+
+/// Inverses the vertex projection of the provided ultrabubbles to the
+/// node ID space of the graph used to construct the original biedged
+/// graph.
+pub fn inverse_map_ultrabubbles(
+    ultrabubbles: FxHashMap<(u64, u64), Vec<(u64, u64)>>,
+) -> FxHashMap<(u64, u64), Vec<(u64, u64)>> {
+    ultrabubbles
+        .into_iter()
+        .map(|((x, y), contained)| {
+            use crate::projection::id_from_black_edge;
+            let x = id_from_black_edge(x);
+            let y = id_from_black_edge(y);
+            let contained = contained
+                .into_iter()
+                .map(|(a, b)| (id_from_black_edge(a), id_from_black_edge(b)))
+                .collect();
+            ((x, y), contained)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn paper_edges() -> Vec<(u64, u64)> {
+        vec![
+            (0, 1),
+            (0, 2),
+            (1, 3),
+            (2, 3),
+            (3, 4),
+            (3, 5),
+            (4, 6),
+            (5, 6),
+            (5, 7),
+            (6, 10),
+            (6, 11),
+            (7, 8),
             (7, 9),
             (8, 9),
             (9, 11),
@@ -1898,12 +2736,14 @@ mod tests {
             (15, 16),
             (15, 17),
             (15, 12),
-        ];
+        ]
+    }
 
-        BiedgedGraph::from_directed_edges(edges).unwrap()
+    fn graph_from_paper() -> BiedgedGraph<Biedged> {
+        BiedgedGraph::from_directed_edges(paper_edges()).unwrap()
     }
 
-    fn example_graph() -> BiedgedGraph<Biedged> {
+    fn example_graph() -> BiedgedGraph<Cactus> {
         /*               -i
                  &     &/
         a--b==c--e==f--h--j
@@ -1916,13 +2756,7 @@ mod tests {
         = 2 black edges
                 */
 
-        let mut graph: BiedgedGraph = BiedgedGraph::new();
-
-        for i in 0..=9 {
-            graph.add_node(i);
-        }
-
-        let edges = vec![
+        let edges = [
             (0, 1),
             (1, 2),
             (1, 2),
@@ -1942,53 +2776,38 @@ mod tests {
             (7, 9),
         ];
 
-        for (a, b) in edges {
-            graph.add_edge(a, b, BiedgedWeight::black(1));
-        }
-
-        graph.max_net_vertex = (graph.graph.node_count() - 1) as u64;
-        graph.max_chain_vertex = graph.max_net_vertex;
-
-        graph
+        BiedgedGraph::from_edges(&edges, &[])
     }
 
     #[test]
     fn simple_contract_all_gray_edges() {
         let edges = vec![(0, 1), (0, 2), (1, 3), (2, 3)];
 
-        let mut graph = BiedgedGraph::from_directed_edges(edges).unwrap();
+        let mut graph: BiedgedGraph<Cactus> =
+            BiedgedGraph::from_directed_edges(edges).unwrap();
 
         let mut proj = Projection::new_for_biedged_graph(&graph);
 
         CactusGraph::contract_all_gray_edges(&mut graph, &mut proj);
 
-        let a = proj.find(0);
-        let b = proj.find(1);
-        let c = proj.find(3);
-        let d = proj.find(7);
+        let a = proj.find(0.into());
+        let b = proj.find(1.into());
+        let c = proj.find(3.into());
+        let d = proj.find(7.into());
 
-        assert_eq!(
-            graph.graph.edge_weight(a, b),
-            Some(&BiedgedWeight::black(1))
-        );
-        assert_eq!(
-            graph.graph.edge_weight(c, d),
-            Some(&BiedgedWeight::black(1))
-        );
-        assert_eq!(
-            graph.graph.edge_weight(b, c),
-            Some(&BiedgedWeight::black(2))
-        );
+        assert_eq!(graph.edge_weight(a, b), Some(BiedgedWeight::black(1)));
+        assert_eq!(graph.edge_weight(c, d), Some(BiedgedWeight::black(1)));
+        assert_eq!(graph.edge_weight(b, c), Some(BiedgedWeight::black(2)));
 
-        assert_eq!(graph.graph.node_count(), 4);
+        assert_eq!(graph.node_count(), 4);
         assert_eq!(graph.black_edge_count(), 4);
         assert_eq!(graph.gray_edge_count(), 0);
-        assert_eq!(graph.graph.edge_count(), 3);
+        assert_eq!(graph.edge_count(), 3);
     }
 
     #[test]
     fn paper_contract_all_gray_edges() {
-        let mut graph: BiedgedGraph = graph_from_paper();
+        let mut graph = graph_from_paper().set_graph_type::<Cactus>();
 
         let mut proj = Projection::new_for_biedged_graph(&graph);
         CactusGraph::contract_all_gray_edges(&mut graph, &mut proj);
@@ -2000,30 +2819,12 @@ mod tests {
             "Expected 18 black edges, is actually {:#?}",
             graph.black_edge_count()
         );
-        assert_eq!(graph.graph.node_count(), 12);
-    }
-
-    fn segment_split_name(
-        name_map: &gfa::gfa::name_conversion::NameMap,
-        n: u64,
-    ) -> Option<String> {
-        use crate::projection::id_from_black_edge;
-        let not_orig = n % 2 != 0;
-        let id = id_from_black_edge(n);
-        let mut name: String = {
-            let bytes = name_map.inverse_map_name(id as usize)?;
-            let name_str = std::str::from_utf8(bytes).unwrap();
-            name_str.into()
-        };
-        if not_orig {
-            name.push('_');
-        }
-        Some(name)
+        assert_eq!(graph.node_count(), 12);
     }
 
     #[test]
     fn edge_contraction_projection() {
-        use crate::projection::id_to_black_edge;
+        use crate::projection::projected_node_name;
         use gfa::{
             gfa::{name_conversion::NameMap, GFA},
             parser::GFAParser,
@@ -2036,11 +2837,24 @@ mod tests {
         let name_map = NameMap::build_from_gfa(&vec_gfa);
         let gfa = name_map.gfa_bytestring_to_usize(&vec_gfa, false).unwrap();
 
-        let mut graph = BiedgedGraph::from_gfa(&gfa);
+        let mut graph =
+            BiedgedGraph::<Biedged>::from_gfa(&gfa).set_graph_type::<Cactus>();
 
         let mut proj = Projection::new_for_biedged_graph(&graph);
 
         CactusGraph::contract_all_gray_edges(&mut graph, &mut proj);
+        proj.build_inverse();
+        let inverse = proj.get_inverse().unwrap();
+
+        // which vertex ends up representing each set of contracted
+        // vertices depends on the union-find, so each set is named
+        // after its smallest vertex instead
+        let set_name = |n: Node| {
+            let smallest = inverse[&proj.find(n).id].iter().min().unwrap();
+            projected_node_name(&name_map, Node::new(*smallest))
+                .unwrap()
+                .to_string()
+        };
 
         let proj_names = vec_gfa
             .segments
@@ -2049,12 +2863,8 @@ mod tests {
                 let orig = name_map.map_name(&s.name).unwrap();
                 let orig_str = std::str::from_utf8(&s.name).unwrap();
                 let orig_name = orig_str.to_string();
-                let (l, r) = id_to_black_edge(orig as u64);
-                let l_end = proj.find(l);
-                let r_end = proj.find(r);
-                let l_end = segment_split_name(&name_map, l_end).unwrap();
-                let r_end = segment_split_name(&name_map, r_end).unwrap();
-                (orig_name, (l_end, r_end))
+                let (left, right) = Node::from_gfa_id(orig as u64);
+                (orig_name, (set_name(left), set_name(right)))
             })
             .collect::<Vec<_>>();
 
@@ -2065,18 +2875,18 @@ mod tests {
             ("d", ("b_", "d_")),
             ("e", ("d_", "e_")),
             ("f", ("d_", "e_")),
-            ("g", ("e_", "k_")),
+            ("g", ("e_", "g_")),
             ("h", ("e_", "h_")),
             ("i", ("h_", "h_")),
-            ("j", ("h_", "k_")),
-            ("k", ("k_", "k_")),
-            ("l", ("k_", "p_")),
-            ("m", ("p_", "m_")),
+            ("j", ("h_", "g_")),
+            ("k", ("g_", "g_")),
+            ("l", ("g_", "l_")),
+            ("m", ("l_", "m_")),
             ("n", ("m_", "n_")),
             ("o", ("m_", "n_")),
-            ("p", ("n_", "p_")),
-            ("q", ("p_", "q_")),
-            ("r", ("p_", "r_")),
+            ("p", ("n_", "l_")),
+            ("q", ("l_", "q_")),
+            ("r", ("l_", "r_")),
         ]
         .into_iter()
         .map(|(a, (l, r))| (a.to_string(), (l.to_string(), r.to_string())))
@@ -2086,46 +2896,1564 @@ mod tests {
     }
 
     #[test]
-    fn cycle_detection() {
-        let graph = example_graph();
+    fn paper_projected_segment_ends() {
+        use crate::projection::{projected_node_name, projected_segment_ends};
+        use gfa::{
+            gfa::{name_conversion::NameMap, GFA},
+            parser::GFAParser,
+        };
 
-        let cycles = CactusGraph::find_cycles(&graph);
+        let parser = GFAParser::new();
+        let vec_gfa: GFA<Vec<u8>, ()> =
+            parser.parse_file("./test/gfas/paper.gfa").unwrap();
 
+        let name_map = NameMap::build_from_gfa(&vec_gfa);
+        let gfa = name_map.gfa_bytestring_to_usize(&vec_gfa, false).unwrap();
+
+        let mut graph =
+            BiedgedGraph::<Biedged>::from_gfa(&gfa).set_graph_type::<Cactus>();
+        let mut proj = Projection::new_for_biedged_graph(&graph);
+        CactusGraph::contract_all_gray_edges(&mut graph, &mut proj);
+
+        let proj_map = proj.projection_map();
+
+        let seg = |name: &str| name_map.map_name(name).unwrap() as u64;
+        let ends = |name: &str| {
+            let (l, r) =
+                projected_segment_ends(&proj_map, &name_map, seg(name))?;
+            Some((l.to_string(), r.to_string()))
+        };
+
+        // the names are those of the vertices each end projects to
+        for id in 0..18 {
+            let (left, right) = Node::from_gfa_id(id);
+            let expected = (
+                projected_node_name(&name_map, proj.find(left)).unwrap(),
+                projected_node_name(&name_map, proj.find(right)).unwrap(),
+            );
+            assert_eq!(
+                projected_segment_ends(&proj_map, &name_map, id),
+                Some(expected)
+            );
+        }
+
+        // nothing else is linked to the start of a
+        assert_eq!(ends("a").unwrap().0, "a");
+
+        // b and c are in the same bubble, and the two ends of i project
+        // to the same vertex, as do the two ends of k
+        assert_eq!(ends("b"), ends("c"));
+        let (i_left, i_right) = ends("i").unwrap();
+        assert_eq!(i_left, i_right);
+        let (k_left, k_right) = ends("k").unwrap();
+        assert_eq!(k_left, k_right);
+
+        // the ends of k are contracted into the end of g
+        assert_eq!(ends("g").unwrap().1, k_left);
+
+        assert_eq!(projected_segment_ends(&proj_map, &name_map, 100), None);
         assert_eq!(
-            cycles,
+            projected_segment_ends(&proj_map, &name_map, u64::MAX / 2 + 1),
+            None
+        );
+        assert_eq!(
+            projected_segment_ends(&proj_map, &name_map, u64::MAX),
+            None
+        );
+    }
+
+    #[test]
+    fn paper_cactus_net_vertices() {
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+
+        let net_vertices =
+            cactus_graph.graph.net_vertices().collect::<FxHashSet<_>>();
+
+        let projected = graph
+            .node_ids()
+            .map(|n| cactus_graph.projection.find(n.into()))
+            .collect::<FxHashSet<_>>();
+
+        assert_eq!(net_vertices.len(), 11);
+        assert_eq!(net_vertices, projected);
+
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let tree_net_vertices =
+            cactus_tree.graph.net_vertices().collect::<FxHashSet<_>>();
+
+        assert_eq!(tree_net_vertices, net_vertices);
+    }
+
+    #[test]
+    fn paper_cactus_tree_chains() {
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+
+        let nets = cactus_tree.net_vertices();
+        let chains = cactus_tree.chains();
+
+        // one net vertex per vertex of the cactus graph, and one
+        // chain vertex per cycle
+        assert_eq!(nets.len(), 11);
+        assert_eq!(chains.len(), cactus_graph.cycles.len());
+        assert_eq!(chains.len(), 8);
+        assert!(chains.iter().all(|&c| cactus_tree.graph.is_chain_vertex(c)));
+
+        let chain_counts = nets
+            .iter()
+            .map(|&n| (n.id, cactus_tree.chain_of(n).len()))
+            .collect::<Vec<_>>();
+
+        // 25 is on a two-edge cycle and two self-loops, while 0, 33,
+        // and 35 are only incident to bridges
+        assert_eq!(
+            chain_counts,
             vec![
-                vec![(1, 2), (2, 1)],
-                vec![(4, 4)],
-                vec![(4, 5), (5, 4)],
-                vec![(7, 7)],
-                vec![(6, 6)],
-                vec![(3, 3)],
-                vec![(6, 7), (7, 5), (5, 6)],
-                vec![(3, 4), (4, 2), (2, 3)],
+                (0, 0),
+                (1, 1),
+                (3, 1),
+                (7, 1),
+                (9, 2),
+                (13, 2),
+                (15, 2),
+                (23, 1),
+                (25, 3),
+                (33, 0),
+                (35, 0),
             ]
         );
+
+        // every chain is adjacent to the net vertices of its cycle
+        let (x, y) = cactus_graph.cycles[0][0];
+        let chain = cactus_tree.chain_of(x);
+        assert_eq!(chain, cactus_tree.chain_of(y));
     }
 
     #[test]
-    fn test_build_cactus_tree() {
-        let mut graph = example_graph();
+    fn disconnected_graph_snarls() {
+        let edges = vec![
+            (0, 1),
+            (0, 2),
+            (1, 3),
+            (2, 3),
+            (4, 5),
+            (4, 6),
+            (5, 7),
+            (6, 7),
+        ];
 
-        let cycles = CactusGraph::find_cycles(&graph);
+        let graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_directed_edges(edges).unwrap();
 
-        let (cycle_chain_map, chain_vertices) =
-            CactusTree::construct_chain_vertices(&mut graph, &cycles);
+        let components = graph.connected_components();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0], (0..8).map(Node::new).collect::<Vec<_>>());
+        assert_eq!(components[1], (8..16).map(Node::new).collect::<Vec<_>>());
 
-        assert_eq!(cycles.len(), chain_vertices.len());
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
 
-        for (edge, chain_vx) in cycle_chain_map.iter() {
-            let chain_edges = graph
-                .graph
-                .edges(*chain_vx)
-                .map(|x| x.1)
-                .collect::<Vec<_>>();
+        let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
 
-            assert!(chain_edges.contains(&edge.0));
-            assert!(chain_edges.contains(&edge.1));
+        for component in components {
+            let in_component = snarl_map.snarls.values().any(|snarl| {
+                component.contains(&snarl.left())
+                    && component.contains(&snarl.right())
+            });
+            assert!(in_component);
+        }
+
+        assert!(snarl_map.get(Node::new(1), Node::new(6)).is_some());
+        assert!(snarl_map.get(Node::new(9), Node::new(14)).is_some());
+    }
+
+    #[test]
+    fn paper_snarl_family() {
+        let graph = graph_from_paper();
+
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+        let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+        let bridge_pairs = [(1, 6), (7, 22)];
+        let chain_pairs = [
+            (2, 4),
+            (3, 5),
+            (8, 10),
+            (9, 11),
+            (12, 14),
+            (13, 19),
+            (15, 18),
+            (24, 31),
+            (25, 30),
+            (26, 28),
+            (26, 29),
+            (27, 28),
+            (27, 29),
+        ];
+
+        let mut expected = SnarlMap::default();
+        expected.extend(
+            bridge_pairs
+                .iter()
+                .map(|&(x, y)| Snarl::bridge_pair(Node::new(x), Node::new(y))),
+        );
+        expected.extend(
+            chain_pairs
+                .iter()
+                .map(|&(x, y)| Snarl::chain_pair(Node::new(x), Node::new(y))),
+        );
+        expected.mark_snarl(Node::new(7), Node::new(22), Node::new(6), true);
+
+        let diff = crate::snarls::diff_snarl_maps(&expected, &snarl_map);
+        assert!(diff.is_empty(), "{:?}", diff);
+
+        let types = snarl_map
+            .to_vec()
+            .iter()
+            .filter(|snarl| snarl.is_bridge_pair())
+            .map(|snarl| (snarl.left().id, snarl.right().id))
+            .collect::<Vec<_>>();
+        assert_eq!(types, bridge_pairs);
+
+        // paper.gfa lists the links in a different order, which mustn't
+        // change which vertices the bridge forest keeps, or the order
+        // the bridges are walked in
+        let parser = gfa::parser::GFAParser::new();
+        let vec_gfa: GFA<Vec<u8>, ()> =
+            parser.parse_file("./test/gfas/paper.gfa").unwrap();
+        let name_map =
+            gfa::gfa::name_conversion::NameMap::build_from_gfa(&vec_gfa);
+        let gfa = name_map.gfa_bytestring_to_usize(&vec_gfa, false).unwrap();
+        let graph = BiedgedGraph::from_gfa(&gfa);
+
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+        let from_gfa = build_snarl_family(&cactus_tree, &bridge_forest);
+        let diff = crate::snarls::diff_snarl_maps(&snarl_map, &from_gfa);
+        assert!(diff.is_empty(), "{:?}", diff);
+    }
+
+    #[test]
+    fn paper_snarl_interior_edges() {
+        let graph = graph_from_paper();
+
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+        let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+        let to_ids = |edges: Vec<(Node, Node)>| {
+            edges
+                .into_iter()
+                .map(|(a, b)| (a.id, b.id))
+                .collect::<Vec<_>>()
+        };
+
+        // The bubble from the right-hand side of segment 0 to the
+        // left-hand side of segment 3, through segments 1 and 2
+        let interior =
+            snarl_map.interior_edges(Node::new(1), Node::new(6), &graph);
+        assert_eq!(
+            to_ids(interior),
+            vec![(1, 2), (1, 4), (2, 3), (3, 6), (4, 5), (5, 6)]
+        );
+
+        // The left-hand sides of segments 1 and 2 face segment 0,
+        // so that's what the snarl between them contains
+        let interior =
+            snarl_map.interior_edges(Node::new(2), Node::new(4), &graph);
+        assert_eq!(to_ids(interior), vec![(0, 1), (1, 2), (1, 4)]);
+
+        // The right-hand sides face the rest of the graph, from
+        // segment 3 on, but the black edges of the boundaries are
+        // never crossed
+        let interior =
+            snarl_map.interior_edges(Node::new(3), Node::new(5), &graph);
+        let interior = to_ids(interior);
+        assert_eq!(interior.len(), 37);
+        assert!(interior.contains(&(6, 7)));
+        assert!(interior.contains(&(34, 35)));
+        assert!(!interior.contains(&(2, 3)));
+        assert!(!interior.contains(&(4, 5)));
+
+        let interior =
+            snarl_map.interior_edges(Node::new(15), Node::new(18), &graph);
+        assert_eq!(
+            to_ids(interior),
+            vec![(15, 16), (15, 18), (16, 17), (17, 18)]
+        );
+
+        let interior =
+            snarl_map.interior_edges(Node::new(2), Node::new(9), &graph);
+        assert!(interior.is_empty());
+
+        // Going around the cycle through segments 12 and 15 leads from
+        // either side of segments 13 and 14 back to their other sides,
+        // so the pairs on that cycle don't separate anything
+        for &(x, y) in [(26, 28), (26, 29), (27, 28), (27, 29)].iter() {
+            let interior =
+                snarl_map.interior_edges(Node::new(x), Node::new(y), &graph);
+            assert!(interior.is_empty(), "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn paper_3_edge_connected_components() {
+        let mut graph = graph_from_paper().set_graph_type::<Cactus>();
+        let mut projection = Projection::new_for_biedged_graph(&graph);
+        CactusGraph::contract_all_gray_edges(&mut graph, &mut projection);
+
+        let components =
+            CactusGraph::find_3_edge_connected_components_nodes(&graph);
+
+        // Only the right-hand sides of segments 12 and 13 end up in
+        // the same nontrivial component
+        assert_eq!(components, vec![vec![Node::new(25), Node::new(27)]]);
+        assert!(components[0].iter().all(|&n| graph.contains_node(n)));
+
+        let raw_components =
+            CactusGraph::find_3_edge_connected_components(&graph);
+        assert_eq!(raw_components, vec![vec![25, 27]]);
+
+        CactusGraph::merge_components(&mut graph, components, &mut projection);
+        assert!(projection.equiv(Node::new(25), Node::new(27)));
+        assert_eq!(graph.net_vertices().count(), 11);
+    }
+
+    #[test]
+    fn sorted_3_edge_connected_components() {
+        // Two disjoint copies of the graph from the paper, with the
+        // edges of the second copy first
+        let paper_edges = paper_edges();
+
+        let edges = paper_edges
+            .iter()
+            .map(|&(a, b)| (a + 18, b + 18))
+            .chain(paper_edges.iter().copied())
+            .collect::<Vec<_>>();
+
+        let mut graph: BiedgedGraph<Cactus> =
+            BiedgedGraph::from_directed_edges(edges).unwrap();
+
+        let mut projection = Projection::new_for_biedged_graph(&graph);
+        CactusGraph::contract_all_gray_edges(&mut graph, &mut projection);
+
+        let components = CactusGraph::find_3_edge_connected_components(&graph);
+        assert_eq!(components, vec![vec![25, 27], vec![61, 63]]);
+
+        #[cfg(feature = "parallel")]
+        assert_eq!(
+            CactusGraph::find_3_edge_connected_components_parallel(&graph),
+            components
+        );
+    }
+
+    #[test]
+    fn merge_components_keeps_min_vertex() {
+        let black = [(0, 1), (1, 3), (3, 5), (5, 6), (2, 4)];
+        let mut graph: BiedgedGraph<Cactus> =
+            BiedgedGraph::from_edges(&black, &[]);
+        let mut projection = Projection::new_for_biedged_graph(&graph);
+
+        let components = vec![
+            vec![Node::new(5), Node::new(3), Node::new(1)],
+            vec![Node::new(4), Node::new(2)],
+        ];
+
+        CactusGraph::merge_components(&mut graph, components, &mut projection);
+
+        let mut nodes = graph.node_ids().collect::<Vec<_>>();
+        nodes.sort_unstable();
+        assert_eq!(nodes, vec![0, 1, 2, 6]);
+
+        for &(n, rep) in [(1, 1), (3, 1), (5, 1), (2, 2), (4, 2)].iter() {
+            assert_eq!(projection.find(Node::new(n)), Node::new(rep));
+        }
+
+        assert_eq!(
+            graph.edge_weight(Node::new(1), Node::new(1)),
+            Some(BiedgedWeight::black(2))
+        );
+        assert!(graph.edge_weight(Node::new(1), Node::new(6)).is_some());
+    }
+
+    #[test]
+    fn merge_large_component() {
+        // a cycle with every edge doubled is 3-edge-connected
+        let black = (0..300)
+            .flat_map(|i| vec![(i, (i + 1) % 300); 2])
+            .collect::<Vec<_>>();
+        let mut graph: BiedgedGraph<Cactus> =
+            BiedgedGraph::from_edges(&black, &[]);
+        let mut projection = Projection::new_for_biedged_graph(&graph);
+
+        let components =
+            CactusGraph::find_3_edge_connected_components_nodes(&graph);
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].len(), 300);
+
+        CactusGraph::merge_components(&mut graph, components, &mut projection);
+
+        assert_eq!(graph.node_ids().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(
+            graph.edge_weight(Node::new(0), Node::new(0)),
+            Some(BiedgedWeight::black(600))
+        );
+
+        for n in 0..300 {
+            assert_eq!(projection.find(Node::new(n)), Node::new(0));
+        }
+    }
+
+    #[test]
+    fn overlapping_components() {
+        let disjoint = vec![
+            vec![Node::new(5), Node::new(3), Node::new(1)],
+            vec![Node::new(4), Node::new(2)],
+        ];
+        assert_eq!(CactusGraph::shared_component_vertex(&disjoint), None);
+
+        let overlapping = vec![
+            vec![Node::new(5), Node::new(3), Node::new(1)],
+            vec![Node::new(4), Node::new(3)],
+        ];
+        assert_eq!(
+            CactusGraph::shared_component_vertex(&overlapping),
+            Some(Node::new(3))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "more than one 3-edge-connected component")]
+    fn merge_overlapping_components() {
+        let black = [(0, 1), (1, 3), (3, 5), (5, 6), (2, 4)];
+        let mut graph: BiedgedGraph<Cactus> =
+            BiedgedGraph::from_edges(&black, &[]);
+        let mut projection = Projection::new_for_biedged_graph(&graph);
+
+        let components = vec![
+            vec![Node::new(5), Node::new(3), Node::new(1)],
+            vec![Node::new(4), Node::new(3)],
+        ];
+
+        CactusGraph::merge_components(&mut graph, components, &mut projection);
+    }
+
+    #[test]
+    fn empty_graph_pipeline() {
+        let gfa: GFA<usize, ()> = GFA::default();
+        let graph = BiedgedGraph::from_gfa(&gfa);
+        assert!(graph.is_empty());
+
+        let (cactus, proj_map) = build_cactus(&graph);
+        assert!(cactus.is_empty());
+        assert!(proj_map.iter().all(|(x, p)| x == p));
+        assert!(net_graph(&graph, &proj_map).is_empty());
+
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        assert!(cactus_graph.graph.is_empty());
+        assert!(cactus_graph.cycles.is_empty());
+        assert!(chain_decomposition(&cactus_graph.graph).is_empty());
+
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        assert!(cactus_tree.chains().is_empty());
+        assert!(cactus_tree.net_vertices().is_empty());
+        assert!(cactus_tree.find_chain_pairs().is_empty());
+
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+        assert!(bridge_forest.tree_edges().is_empty());
+        assert!(bridge_forest.leaves().is_empty());
+        assert!(bridge_forest.find_bridge_pairs().is_empty());
+
+        let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+        assert!(snarl_map.snarls.is_empty());
+        assert!(find_ultrabubbles(&cactus_tree, &bridge_forest).is_empty());
+
+        assert!(ultrabubbles(&gfa).is_empty());
+        assert_eq!(
+            decomposition_summary(&gfa),
+            DecompositionSummary::default()
+        );
+
+        let (snarl_map, _) = snarls_from_gfa_traced(&gfa);
+        assert!(snarl_map.snarls.is_empty());
+
+        let mut streamed = 0;
+        snarls_from_gfa_streaming(&gfa, |_| streamed += 1);
+        assert_eq!(streamed, 0);
+
+        let subgraph = snarls_in_subgraph(&graph, &FxHashSet::default());
+        assert!(subgraph.snarls.is_empty());
+    }
+
+    #[test]
+    fn paper_chain_decomposition() {
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+
+        let chains = chain_decomposition(&cactus_graph.graph);
+        let cycle_counts =
+            chains.iter().map(|chain| chain.len()).collect::<Vec<_>>();
+        assert_eq!(cycle_counts, vec![1, 2, 1]);
+
+        let nodes = |ids: &[u64]| -> Vec<Node> {
+            ids.iter().map(|&n| Node::new(n)).collect()
+        };
+
+        // the two cycles of the second chain meet at vertex 9
+        assert_eq!(chains[1], vec![nodes(&[7, 9]), nodes(&[9, 13, 15])]);
+    }
+
+    #[test]
+    fn streaming_snarls_match_collected() {
+        let parser = gfa::parser::GFAParser::new();
+
+        for path in
+            ["./test/gfas/paper_u64.gfa", "./test/gfas/paper_2_u64.gfa"].iter()
+        {
+            let gfa: GFA<usize, ()> = parser.parse_file(path).unwrap();
+
+            let mut streamed = Vec::new();
+            snarls_from_gfa_streaming(&gfa, |snarl| streamed.push(snarl));
+            let count = streamed.len();
+
+            let streamed = streamed.into_iter().collect::<FxHashSet<_>>();
+            assert_eq!(streamed.len(), count);
+
+            let (snarl_map, _) = snarls_from_gfa_traced(&gfa);
+            let collected =
+                snarl_map.snarls.values().copied().collect::<FxHashSet<_>>();
+
+            assert_eq!(streamed, collected);
+        }
+    }
+
+    #[test]
+    fn undirected_gfa_snarls() {
+        let parser = gfa::parser::GFAParser::new();
+        let gfa: GFA<usize, ()> =
+            parser.parse_file("./test/gfas/paper_u64.gfa").unwrap();
+
+        let snarl_count = |graph: &BiedgedGraph<Biedged>| {
+            let cactus_graph = CactusGraph::from_biedged_graph(graph);
+            let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+            let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+            build_snarl_family(&cactus_tree, &bridge_forest)
+                .snarls
+                .len()
+        };
+
+        let oriented = BiedgedGraph::from_gfa(&gfa);
+        let undirected = BiedgedGraph::from_gfa_undirected(&gfa);
+
+        // every link in the paper graph is forward, so ignoring the
+        // orientations changes nothing
+        assert!(oriented == undirected);
+        assert_eq!(snarl_count(&oriented), snarl_count(&undirected));
+
+        // writing the link from 13 to 15 from the other strand gives
+        // the same graph, whether or not the orientations are used
+        let mut flipped = gfa.clone();
+        for link in flipped.links.iter_mut() {
+            if (link.from_segment, link.to_segment) == (13, 15) {
+                std::mem::swap(&mut link.from_segment, &mut link.to_segment);
+                link.from_orient = gfa::gfa::Orientation::Backward;
+                link.to_orient = gfa::gfa::Orientation::Backward;
+            }
+        }
+
+        assert!(BiedgedGraph::from_gfa(&flipped) == oriented);
+
+        let flipped = BiedgedGraph::from_gfa_undirected(&flipped);
+        assert!(flipped == undirected);
+        assert_eq!(
+            flipped.edge_weight(Node::new(27), Node::new(30)),
+            Some(BiedgedWeight::gray(1))
+        );
+        assert_eq!(snarl_count(&oriented), 15);
+        assert_eq!(snarl_count(&flipped), 15);
+
+        // a link from the end of 13 to the end of 15 can also be
+        // written from either strand. It changes the oriented graph, but
+        // the undirected graph is the same as for the forward link
+        let mut inverted = gfa.clone();
+        let mut reinverted = gfa.clone();
+        for (a, b) in inverted.links.iter_mut().zip(reinverted.links.iter_mut())
+        {
+            if (a.from_segment, a.to_segment) == (13, 15) {
+                a.to_orient = gfa::gfa::Orientation::Backward;
+
+                std::mem::swap(&mut b.from_segment, &mut b.to_segment);
+                b.to_orient = gfa::gfa::Orientation::Backward;
+            }
+        }
+
+        let inverted_oriented = BiedgedGraph::from_gfa(&inverted);
+        assert!(BiedgedGraph::from_gfa(&reinverted) == inverted_oriented);
+        assert!(inverted_oriented != oriented);
+
+        let inverted = BiedgedGraph::from_gfa_undirected(&inverted);
+        assert!(BiedgedGraph::from_gfa_undirected(&reinverted) == inverted);
+        assert!(inverted == undirected);
+    }
+
+    #[test]
+    fn traced_pipeline_stages() {
+        let parser = gfa::parser::GFAParser::new();
+        let gfa: GFA<usize, ()> =
+            parser.parse_file("./test/gfas/paper_u64.gfa").unwrap();
+
+        let (snarl_map, trace) = snarls_from_gfa_traced(&gfa);
+
+        let names = trace
+            .stages
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["biedged", "gray contraction", "component merge"]
+        );
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace.three_edge_components, 1);
+
+        let counts = trace.edge_counts();
+        assert_eq!(counts, vec![42, 15, 14]);
+        assert!(counts.windows(2).all(|w| w[1] < w[0]));
+
+        assert_eq!(snarl_map.snarls.len(), 15);
+
+        // the last stage is the graph the cactus graph is built from
+        let biedged = BiedgedGraph::from_gfa(&gfa);
+        let mut trace = PipelineTrace::default();
+        let cactus_graph =
+            CactusGraph::from_biedged_graph_traced(&biedged, &mut trace);
+        assert!(trace.stages[2].1 == cactus_graph.graph);
+        assert!(
+            CactusGraph::from_biedged_graph(&biedged).graph
+                == cactus_graph.graph
+        );
+    }
+
+    #[test]
+    fn paper_subgraph_snarls() {
+        let graph = graph_from_paper();
+
+        let snarl_set = |snarl_map: &SnarlMap| {
+            let mut snarls = snarl_map
+                .snarls
+                .values()
+                .map(|s| (s.left().id, s.right().id))
+                .collect::<Vec<_>>();
+            snarls.sort_unstable();
+            snarls
+        };
+
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+        let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+        // the region covering the entire graph finds the same snarls
+        let region = (0..=17).collect::<FxHashSet<u64>>();
+        let full = snarls_in_subgraph(&graph, &region);
+        assert_eq!(snarl_set(&full), snarl_set(&snarl_map));
+
+        // segments 10 through 17 keep the snarls entirely inside
+        // them, and gain a new one at the edge of the region
+        let region = (10..=17).collect::<FxHashSet<u64>>();
+        let sub = snarls_in_subgraph(&graph, &region);
+        assert_eq!(
+            snarl_set(&sub),
+            vec![
+                (21, 22),
+                (24, 31),
+                (25, 30),
+                (26, 28),
+                (26, 29),
+                (27, 28),
+                (27, 29)
+            ]
+        );
+    }
+
+    #[test]
+    fn paper_inverted_projection() {
+        use crate::projection::invert_projection;
+
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+
+        let proj_map = cactus_graph.projection.projection_map();
+        let inverse = invert_projection(&proj_map);
+        // the 3-edge-connected component containing 25 and 27 also
+        // includes the vertices they were joined to by gray edges
+        assert_eq!(inverse[&25], (25..=30).collect::<Vec<_>>());
+        assert_eq!(inverse[&33], vec![33]);
+
+        for (projected, from) in inverse.iter() {
+            assert!(from.contains(projected));
+            assert!(from.iter().all(|x| proj_map[x] == *projected));
+        }
+
+        let total = inverse.values().map(|from| from.len()).sum::<usize>();
+        assert_eq!(total, proj_map.len());
+    }
+
+    #[test]
+    fn compressed_projection_lookups() {
+        use crate::projection::{
+            find_projection, find_projection_compress, invert_projection,
+        };
+
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+
+        let proj_map = cactus_graph.projection.projection_map();
+
+        // Chain each vertex to the previous one in its component, as
+        // if they had been contracted one at a time
+        let mut chained: BTreeMap<u64, u64> = BTreeMap::new();
+        for (projected, from) in invert_projection(&proj_map) {
+            let mut prev = projected;
+            for x in from.into_iter().filter(|&x| x != projected) {
+                chained.insert(x, prev);
+                prev = x;
+            }
+        }
+
+        assert_eq!(chained[&30], 29);
+
+        for &x in proj_map.keys() {
+            assert_eq!(find_projection(&chained, x), proj_map[&x]);
+        }
+
+        let mut compressed = chained.clone();
+        for &x in proj_map.keys().rev() {
+            let uncompressed = find_projection(&chained, x);
+            assert_eq!(
+                find_projection_compress(&mut compressed, x),
+                uncompressed
+            );
+        }
+
+        // every chain now leads directly to its projection
+        for (x, projected) in compressed.iter() {
+            assert_eq!(*projected, proj_map[x]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "projection map has a cycle")]
+    fn cyclic_projection_lookup() {
+        use crate::projection::find_projection;
+
+        let proj_map: BTreeMap<u64, u64> =
+            vec![(1, 2), (2, 1), (3, 3)].into_iter().collect();
+
+        assert_eq!(find_projection(&proj_map, 3), 3);
+        assert_eq!(find_projection(&proj_map, 4), 4);
+
+        find_projection(&proj_map, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "projection map has a cycle")]
+    fn cyclic_projection_compress() {
+        use crate::projection::find_projection_compress;
+
+        let mut proj_map: BTreeMap<u64, u64> =
+            vec![(1, 2), (2, 1), (5, 1)].into_iter().collect();
+
+        find_projection_compress(&mut proj_map, 5);
+    }
+
+    #[test]
+    fn paper_cactus_cut_vertices() {
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+
+        let mut cut_vertices = cactus_graph
+            .graph
+            .cut_vertices()
+            .into_iter()
+            .map(|n| n.id)
+            .collect::<Vec<_>>();
+        cut_vertices.sort_unstable();
+
+        // 1, 3, and 7 join bridges and two-edge cycles, 9 and 13 are
+        // on the three-edge cycle, and 23 joins a cycle with three
+        // bridges. 15 is only on the three-edge cycle, and a self-loop
+        assert_eq!(cut_vertices, vec![1, 3, 7, 9, 13, 23]);
+    }
+
+    #[test]
+    fn minimum_contained_snarls() {
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+        let all = build_snarl_family(&cactus_tree, &bridge_forest);
+        let filtered = build_snarl_family_with(
+            &cactus_tree,
+            &bridge_forest,
+            SnarlMapBuilder::new().min_contained(1),
+        );
+
+        // only the bridge pair (7, 22) contains a black edge, namely
+        // the bridge between them
+        assert_eq!(all.snarls.len(), 15);
+        assert_eq!(filtered.snarls.len(), 1);
+
+        let (x, y) = (Node::new(7), Node::new(22));
+        assert_eq!(filtered.get(x, y), all.get(x, y));
+        assert_eq!(filtered.get_snarl_ix(x, y), all.get_snarl_ix(x, y));
+        assert_eq!(filtered.contained_count(x, y), Some(1));
+        assert_eq!(filtered.with_boundary(x).count(), 1);
+
+        assert!(all.get(Node::new(2), Node::new(4)).is_some());
+        assert!(filtered.get(Node::new(2), Node::new(4)).is_none());
+
+        // the filter doesn't depend on the order the graph was built in
+        let parser = gfa::parser::GFAParser::new();
+        let gfa: GFA<usize, ()> =
+            parser.parse_file("./test/gfas/paper_u64.gfa").unwrap();
+        let graph = BiedgedGraph::from_gfa(&gfa);
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+        let from_gfa = build_snarl_family_with(
+            &cactus_tree,
+            &bridge_forest,
+            SnarlMapBuilder::new().min_contained(1),
+        );
+        let diff = crate::snarls::diff_snarl_maps(&filtered, &from_gfa);
+        assert!(diff.is_empty(), "{:?}", diff);
+    }
+
+    #[test]
+    fn paper_build_cactus() {
+        let graph = graph_from_paper();
+        let edges_before = graph.edge_count();
+
+        let (cactus, proj_map) = build_cactus(&graph);
+
+        assert_eq!(graph.edge_count(), edges_before);
+
+        assert_eq!(cactus.node_count(), 11);
+        assert_eq!(cactus.edge_count(), 14);
+        assert_eq!(cactus.black_edge_count(), 18);
+        assert_eq!(cactus.gray_edge_count(), 0);
+
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        assert_eq!(proj_map, cactus_graph.projection.projection_map());
+    }
+
+    #[test]
+    fn paper_net_graph() {
+        let graph = graph_from_paper();
+        let (cactus, proj_map) = build_cactus(&graph);
+
+        let quotient = net_graph(&graph, &proj_map);
+
+        let representatives = graph
+            .node_ids()
+            .map(|n| proj_map[&n])
+            .collect::<FxHashSet<_>>();
+        assert_eq!(quotient.node_count(), representatives.len());
+        assert_eq!(quotient.node_count(), 11);
+
+        assert_eq!(quotient.black_edge_count(), graph.black_edge_count());
+        assert_eq!(quotient.gray_edge_count(), graph.gray_edge_count());
+        assert!(quotient.gray_edges().all(|(a, b, _)| a == b));
+
+        assert!(quotient.black_subgraph().set_graph_type::<Cactus>() == cactus);
+    }
+
+    #[test]
+    fn pipeline_stages_logged() {
+        use log::{Level, Log, Metadata, Record};
+        use std::sync::Mutex;
+
+        struct CaptureLogger(Mutex<Vec<(Level, String)>>);
+
+        impl Log for CaptureLogger {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn log(&self, record: &Record<'_>) {
+                let msg = record.args().to_string();
+                self.0.lock().unwrap().push((record.level(), msg));
+            }
+
+            fn flush(&self) {}
+        }
+
+        static LOGGER: CaptureLogger = CaptureLogger(Mutex::new(Vec::new()));
+
+        // other tests may log concurrently, so only the presence of
+        // the messages is checked
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(log::LevelFilter::Trace);
+        }
+
+        let graph = graph_from_paper();
+        let _ = build_cactus(&graph);
+
+        let records = LOGGER.0.lock().unwrap();
+
+        for stage in [
+            "contracting gray edges",
+            "finding 3-edge-connected components",
+            "merging 3-edge-connected components",
+        ]
+        .iter()
+        {
+            assert!(records
+                .iter()
+                .any(|(level, msg)| *level == Level::Debug && msg == stage));
+        }
+
+        assert!(records.iter().any(|(_, msg)| msg.starts_with("  took")));
+    }
+
+    #[test]
+    fn paper_graph_equality() {
+        let gfa_graph = {
+            let parser = gfa::parser::GFAParser::new();
+            let gfa: GFA<usize, ()> =
+                parser.parse_file("./test/gfas/paper_u64.gfa").unwrap();
+            BiedgedGraph::<Biedged>::from_gfa(&gfa)
+        };
+
+        assert!(graph_from_paper() == graph_from_paper());
+        assert_eq!(
+            graph_from_paper().canonical_hash(),
+            graph_from_paper().canonical_hash()
+        );
+
+        // the edge list and the GFA describe the same graph
+        assert!(graph_from_paper() == gfa_graph);
+        assert_eq!(
+            graph_from_paper().canonical_hash(),
+            gfa_graph.canonical_hash()
+        );
+    }
+
+    #[test]
+    fn paper_black_and_gray_subgraphs() {
+        let graph = graph_from_paper();
+
+        let black = graph.black_subgraph();
+        let gray = graph.gray_subgraph();
+
+        assert_eq!(black.node_count(), graph.node_count());
+        assert_eq!(gray.node_count(), graph.node_count());
+
+        // one black edge for each of the 18 segments, connecting the
+        // two sides of the segment
+        assert_eq!(black.edge_count(), 18);
+        assert_eq!(black.black_edge_count(), 18);
+        assert_eq!(black.gray_edge_count(), 0);
+        assert!(black
+            .edges()
+            .all(|(a, b, w)| Node::new(a).opposite().id == b && w.black == 1));
+
+        assert_eq!(gray.black_edge_count(), 0);
+        assert_eq!(gray.gray_edge_count(), graph.gray_edge_count());
+        assert_eq!(gray.gray_edge_count(), paper_edges().len());
+    }
+
+    #[test]
+    fn edge_counts_use_multiplicity() {
+        let mut graph = graph_from_paper().set_graph_type::<Cactus>();
+        let mut projection = Projection::new_for_biedged_graph(&graph);
+
+        CactusGraph::contract_all_gray_edges(&mut graph, &mut projection);
+
+        let summed =
+            graph.black_edges().map(|(_, _, w)| w.black).sum::<usize>();
+
+        // contracting the gray edges leaves some black edges parallel,
+        // which petgraph stores as a single edge
+        assert!(graph.black_edges().count() < summed);
+        assert_eq!(graph.black_edge_count(), summed);
+        assert_eq!(graph.black_edge_count(), 18);
+        assert_eq!(graph.gray_edge_count(), 0);
+    }
+
+    #[test]
+    fn paper_decomposition_summary() {
+        use gfa::{gfa::name_conversion::NameMap, parser::GFAParser};
+
+        let parser = GFAParser::new();
+        let vec_gfa: GFA<Vec<u8>, ()> =
+            parser.parse_file("./test/gfas/paper.gfa").unwrap();
+
+        let name_map = NameMap::build_from_gfa(&vec_gfa);
+        let gfa = name_map.gfa_bytestring_to_usize(&vec_gfa, false).unwrap();
+
+        let summary = decomposition_summary(&gfa);
+
+        assert_eq!(
+            summary,
+            DecompositionSummary {
+                nodes: 36,
+                black_edges: 18,
+                gray_edges: 24,
+                three_edge_components: 1,
+                cycles: 8,
+                chain_pairs: 13,
+                bridge_pairs: 2,
+                // only (7, 22) contains a black bridge edge
+                max_depth: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn resume_from_contracted_graph() {
+        let mut contracted = graph_from_paper().set_graph_type::<Cactus>();
+        assert!(!contracted.is_cactus_ready());
+
+        let mut projection = Projection::new_for_biedged_graph(&contracted);
+        CactusGraph::contract_all_gray_edges(&mut contracted, &mut projection);
+        assert!(contracted.is_cactus_ready());
+
+        // a graph that was saved after contracting its gray edges,
+        // without its projection
+        let black = contracted
+            .black_edges()
+            .flat_map(|(a, b, w)| std::iter::repeat_n((a.id, b.id), w.black))
+            .collect::<Vec<_>>();
+        let mut saved: BiedgedGraph<Cactus> =
+            BiedgedGraph::from_edges(&black, &[]);
+        assert!(saved == contracted);
+
+        let components =
+            CactusGraph::find_3_edge_connected_components_nodes(&saved);
+        assert_eq!(components, vec![vec![Node::new(25), Node::new(27)]]);
+
+        let mut projection = Projection::new_for_biedged_graph(&saved);
+        CactusGraph::merge_components(&mut saved, components, &mut projection);
+        assert_eq!(saved.node_count(), 11);
+
+        let saved = saved.set_graph_type::<Biedged>();
+        let cactus_graph = CactusGraph::from_biedged_graph(&saved);
+        assert_eq!(cactus_graph.graph.node_count(), 11);
+    }
+
+    #[test]
+    fn contract_duplicated_links() {
+        use gfa::gfa::Orientation::{Backward, Forward};
+
+        // segment 0 is linked to segment 1 twice, and segment 1 to
+        // segment 2 once; the last link connects segment 2's end to
+        // itself
+        let edges = vec![
+            (0, Forward, 1, Forward),
+            (0, Forward, 1, Forward),
+            (1, Forward, 2, Forward),
+            (2, Forward, 2, Backward),
+        ];
+
+        let mut graph: BiedgedGraph<Cactus> =
+            BiedgedGraph::from_bidirected_edges(edges).unwrap();
+
+        assert_eq!(
+            graph.edge_weight(Node::new(1), Node::new(2)),
+            Some(BiedgedWeight::gray(2))
+        );
+        assert_eq!(graph.gray_edge_count(), 4);
+
+        let mut projection = Projection::new_for_biedged_graph(&graph);
+        CactusGraph::contract_all_gray_edges(&mut graph, &mut projection);
+
+        assert_eq!(graph.gray_edge_count(), 0);
+        assert_eq!(graph.black_edge_count(), 3);
+
+        assert!(projection.equiv(Node::new(1), Node::new(2)));
+        assert!(projection.equiv(Node::new(3), Node::new(4)));
+        assert!(!projection.equiv(Node::new(1), Node::new(3)));
+        assert!(!projection.equiv(Node::new(0), Node::new(1)));
+        assert_eq!(projection.find(Node::new(5)), Node::new(5));
+
+        assert_eq!(graph.node_count(), 4);
+    }
+
+    #[test]
+    fn paper_cactus_dot() {
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+
+        let cycles = chain_decomposition(&cactus_graph.graph)
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        assert_eq!(cycles.len(), 4);
+
+        let dot = cactus_to_dot(&cactus_graph.graph, &cycles);
+
+        assert!(dot.starts_with("graph cactus {\n"));
+        assert!(dot.ends_with("}\n"));
+
+        let colors = dot
+            .lines()
+            .filter_map(|line| line.split("color=").nth(1))
+            .map(|rest| rest.trim_end_matches("];"))
+            .filter(|&color| color != "black")
+            .collect::<FxHashSet<_>>();
+
+        assert_eq!(colors.len(), cycles.len());
+
+        // the two parallel edges forming the cycle [7, 9] share its
+        // color, while the bridge between 23 and 33 is black
+        assert_eq!(dot.matches("    7 -- 9 [color=blue];").count(), 2);
+        assert!(dot.contains("    23 -- 33 [color=black];"));
+    }
+
+    #[test]
+    fn paper_graph_bridges() {
+        let graph = graph_from_paper();
+
+        let bridges = graph
+            .bridges()
+            .into_iter()
+            .map(|(a, b)| (a.id, b.id))
+            .collect::<Vec<_>>();
+
+        // The black edges of the segments at the ends of the graph,
+        // and of the two segments between the bubble chains
+        assert_eq!(bridges, vec![(0, 1), (6, 7), (22, 23), (32, 33), (34, 35)]);
+    }
+
+    #[test]
+    fn paper_bridge_forest_tree() {
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+        let edges = bridge_forest.tree_edges();
+        let node_count = bridge_forest.graph.node_count();
+
+        // the paper graph is connected, so the forest is a single
+        // tree, with one edge for each bridge of the cactus graph
+        assert_eq!(node_count, 6);
+        assert_eq!(edges.len(), node_count - 1);
+        assert_eq!(edges.len(), graph.bridges().len());
+
+        let ids = |nodes: Vec<Node>| {
+            nodes.into_iter().map(|n| n.id).collect::<Vec<_>>()
+        };
+
+        // the three tips of the graph, at the start of segment 0 and
+        // the ends of segments 16 and 17
+        assert_eq!(ids(bridge_forest.leaves()), vec![0, 33, 35]);
+
+        let path = bridge_forest.path_between(Node::new(0), Node::new(35));
+        assert_eq!(path.map(ids), Some(vec![0, 1, 7, 23, 35]));
+
+        let back = bridge_forest.path_between(Node::new(35), Node::new(0));
+        assert_eq!(back.map(ids), Some(vec![35, 23, 7, 1, 0]));
+
+        // 10 and 12 are both in the cycles projected to 7
+        let path = bridge_forest.path_between(Node::new(10), Node::new(12));
+        assert_eq!(path.map(ids), Some(vec![7]));
+    }
+
+    #[test]
+    fn snarl_length_from_segment_meta() {
+        use gfa::gfa::{Link, Orientation, Segment, GFA};
+
+        let seqs: [&[u8]; 4] = [b"ACGTACGTAC", b"GGA", b"TTAAC", b"CCCTGGA"];
+
+        let mut gfa: GFA<usize, ()> = GFA::default();
+
+        for (name, seq) in seqs.iter().enumerate() {
+            gfa.segments.push(Segment {
+                name,
+                sequence: seq.to_vec(),
+                optional: (),
+            });
+        }
+
+        for &(from, to) in [(0, 1), (0, 2), (1, 3), (2, 3)].iter() {
+            gfa.links.push(Link {
+                from_segment: from,
+                from_orient: Orientation::Forward,
+                to_segment: to,
+                to_orient: Orientation::Forward,
+                overlap: b"0M".to_vec(),
+                optional: (),
+            });
+        }
+
+        let (graph, meta) =
+            BiedgedGraph::<Biedged>::from_gfa_with(&gfa, |s| s.sequence.len());
+
+        assert_eq!(meta.len(), 4);
+        assert_eq!(meta.edge_meta(Node::new(1)), Some(&10));
+        assert_eq!(meta.edge_meta(Node::new(6)), Some(&7));
+
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+        let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+        let interior =
+            snarl_map.interior_edges(Node::new(1), Node::new(6), &graph);
+
+        let snarl_length: usize = interior
+            .into_iter()
+            .filter(|(a, b)| a.opposite() == *b)
+            .filter_map(|(a, _)| meta.edge_meta(a))
+            .sum();
+
+        assert_eq!(snarl_length, 3 + 5);
+    }
+
+    #[test]
+    fn rotated_cycles() {
+        let cycle = [3, 8, 1, 6, 4]
+            .iter()
+            .map(|&n| Node::new(n))
+            .collect::<Vec<_>>();
+
+        for (ix, &start) in cycle.iter().enumerate() {
+            let rotated = rotate_cycle(&cycle, start).unwrap();
+            assert_eq!(rotated[0], start);
+            assert_eq!(rotated.len(), cycle.len());
+            for (offset, &n) in rotated.iter().enumerate() {
+                assert_eq!(n, cycle[(ix + offset) % cycle.len()]);
+            }
+        }
+
+        assert_eq!(rotate_cycle(&cycle, Node::new(5)), None);
+        assert_eq!(rotate_cycle(&[], Node::new(5)), None);
+    }
+
+    #[test]
+    fn canonical_cycles() {
+        let edges = |ids: &[(u64, u64)]| {
+            ids.iter()
+                .map(|&(a, b)| (Node::new(a), Node::new(b)))
+                .collect::<Vec<_>>()
+        };
+
+        let canonical = edges(&[(2, 3), (3, 9), (9, 4), (4, 2)]);
+
+        let rotated = edges(&[(9, 4), (4, 2), (2, 3), (3, 9)]);
+        let reversed = edges(&[(3, 2), (2, 4), (4, 9), (9, 3)]);
+
+        assert_eq!(canonicalize_cycle(&canonical), canonical);
+        assert_eq!(canonicalize_cycle(&rotated), canonical);
+        assert_eq!(canonicalize_cycle(&reversed), canonical);
+
+        let self_loop = edges(&[(5, 5)]);
+        assert_eq!(canonicalize_cycle(&self_loop), self_loop);
+
+        assert_eq!(
+            canonicalize_cycle(&edges(&[(8, 6), (6, 8)])),
+            edges(&[(6, 8), (8, 6)])
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_3_edge_connected_components() {
+        // Two disjoint copies of the graph from the paper
+        let paper_edges = paper_edges();
+
+        let edges = paper_edges
+            .iter()
+            .copied()
+            .chain(paper_edges.iter().map(|&(a, b)| (a + 18, b + 18)))
+            .collect::<Vec<_>>();
+
+        let mut graph: BiedgedGraph<Cactus> =
+            BiedgedGraph::from_directed_edges(edges).unwrap();
+
+        let mut projection = Projection::new_for_biedged_graph(&graph);
+        CactusGraph::contract_all_gray_edges(&mut graph, &mut projection);
+
+        assert_eq!(graph.connected_components().len(), 2);
+
+        let sorted = |mut comps: Vec<Vec<usize>>| {
+            comps.iter_mut().for_each(|c| c.sort_unstable());
+            comps.sort_unstable();
+            comps
+        };
+
+        let serial =
+            sorted(CactusGraph::find_3_edge_connected_components(&graph));
+        let parallel = sorted(
+            CactusGraph::find_3_edge_connected_components_parallel(&graph),
+        );
+
+        assert_eq!(serial.len(), 2);
+        assert_eq!(serial, parallel);
+    }
+
+    #[test]
+    fn chain_pairs_from_cycle() {
+        // The cycle formed by the two parallel segments 1 and 2 in the
+        // paper graph, going forward through segment 1 and backward
+        // through segment 2
+        let cycle = [2, 3, 5, 4]
+            .iter()
+            .map(|&n| Node::new(n))
+            .collect::<Vec<_>>();
+
+        let chain_pairs = cycle_chain_pairs(&cycle);
+
+        assert_eq!(
+            chain_pairs,
+            vec![
+                Snarl::chain_pair(Node::new(3), Node::new(5)),
+                Snarl::chain_pair(Node::new(2), Node::new(4)),
+            ]
+        );
+
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+        let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+        for snarl in chain_pairs {
+            assert_eq!(snarl_map.get(snarl.left(), snarl.right()), Some(snarl));
+        }
+
+        assert!(cycle_chain_pairs(&cycle[..1]).is_empty());
+    }
+
+    #[test]
+    fn cycle_detection() {
+        let graph = example_graph();
+
+        let cycles = CactusGraph::find_cycles(&graph)
+            .into_iter()
+            .map(|cycle| cycle.into_iter().map(|(a, b)| (a.id, b.id)).collect())
+            .collect::<Vec<Vec<_>>>();
+
+        assert_eq!(
+            cycles,
+            vec![
+                vec![(1, 2), (2, 1)],
+                vec![(4, 4)],
+                vec![(4, 5), (5, 4)],
+                vec![(7, 7)],
+                vec![(6, 6)],
+                vec![(3, 3)],
+                vec![(5, 6), (6, 7), (7, 5)],
+                vec![(2, 3), (3, 4), (4, 2)],
+            ]
+        );
+    }
+
+    #[test]
+    fn cycle_edge_weights() {
+        let graph = example_graph();
+
+        let nodes =
+            |ids: &[u64]| ids.iter().map(|&n| Node::new(n)).collect::<Vec<_>>();
+        let edges = |ids: &[u64]| {
+            cycle_edges(&nodes(ids), &graph)
+                .into_iter()
+                .map(|(a, b, w)| (a.id, b.id, w.black))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(edges(&[2, 3, 4]), vec![(2, 3, 1), (3, 4, 1), (4, 2, 1)]);
+        assert_eq!(edges(&[5, 6, 7]), vec![(5, 6, 1), (6, 7, 1), (7, 5, 1)]);
+
+        // a two-edge cycle goes both ways along the same doubled edge
+        assert_eq!(edges(&[1, 2]), vec![(1, 2, 2), (2, 1, 2)]);
+        assert_eq!(edges(&[4]), vec![(4, 4, 1)]);
+
+        // 2 and 5 aren't adjacent
+        assert_eq!(edges(&[2, 5, 4]), vec![(5, 4, 2), (4, 2, 1)]);
+        assert!(edges(&[]).is_empty());
+    }
+
+    #[test]
+    fn paper_cactus_cycles() {
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+
+        let cycles = CactusGraph::find_cycles(&cactus_graph.graph);
+
+        // each cycle is closed, with every edge starting where the
+        // previous one ended
+        for cycle in cycles.iter() {
+            let len = cycle.len();
+            for (ix, &(_, to)) in cycle.iter().enumerate() {
+                assert_eq!(to, cycle[(ix + 1) % len].0);
+            }
+        }
+
+        let mut cycles = cycles
+            .into_iter()
+            .map(|cycle| {
+                canonicalize_cycle(&cycle)
+                    .into_iter()
+                    .map(|(a, b)| (a.id, b.id))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        cycles.sort();
+
+        // 25 has two black self-loops, and the only cycle with more
+        // than two edges is the one through 9, 13, and 15
+        assert_eq!(
+            cycles,
+            vec![
+                vec![(1, 3), (3, 1)],
+                vec![(7, 9), (9, 7)],
+                vec![(9, 13), (13, 15), (15, 9)],
+                vec![(13, 13)],
+                vec![(15, 15)],
+                vec![(23, 25), (25, 23)],
+                vec![(25, 25)],
+                vec![(25, 25)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_cactus_tree() {
+        let mut graph = example_graph();
+
+        let cycles = CactusGraph::find_cycles(&graph);
+
+        let (cycle_chain_map, chain_vertices) =
+            CactusTree::construct_chain_vertices(&mut graph, &cycles);
+
+        assert_eq!(cycles.len(), chain_vertices.len());
+
+        for (edge, chain_vx) in cycle_chain_map.iter() {
+            let chain_edges = graph
+                .graph
+                .edges(*chain_vx)
+                .map(|x| x.1)
+                .collect::<Vec<_>>();
+
+            assert!(chain_edges.contains(&edge.0));
+            assert!(chain_edges.contains(&edge.1));
+        }
+    }
+
+    #[test]
+    fn paper_black_edge_chain_vertices() {
+        let graph = graph_from_paper();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+
+        // one chain vertex is added for each cycle, in order
+        let chains = cactus_tree.chains();
+        assert_eq!(chains.len(), cactus_graph.cycles.len());
+
+        // the canonical cycles can run from a larger vertex to a
+        // smaller one, so the chain vertices are keyed by sorted edge to
+        // match black_edge_projection. Parallel black edges can be in
+        // different cycles, and then either chain vertex will do
+        for segment in 0..18 {
+            let (left, right) = Node::from_gfa_id(segment);
+
+            let cycles = match cactus_graph.black_edge_cycle(left) {
+                Some(cycles) => cycles,
+                None => {
+                    assert_eq!(cactus_tree.black_edge_chain_vertex(left), None);
+                    continue;
+                }
+            };
+
+            let chain_vx = cactus_tree.black_edge_chain_vertex(left).unwrap();
+            assert!(cycles.iter().any(|&cycle| chains[cycle] == chain_vx));
+            assert_eq!(
+                cactus_tree.black_edge_chain_vertex(right),
+                Some(chain_vx)
+            );
+        }
+    }
+
+    #[test]
+    fn paper_ultrabubbles() {
+        use gfa::{
+            gfa::{name_conversion::NameMap, Orientation},
+            parser::GFAParser,
+        };
+
+        use bstr::ByteSlice;
+
+        let parser = GFAParser::new();
+        let vec_gfa: GFA<Vec<u8>, ()> =
+            parser.parse_file("./test/gfas/paper.gfa").unwrap();
+
+        let name_map = NameMap::build_from_gfa(&vec_gfa);
+        let gfa = name_map.gfa_bytestring_to_usize(&vec_gfa, false).unwrap();
+
+        let oriented = |(id, orient): (usize, Orientation)| {
+            let name = name_map.inverse_map_name(id).unwrap();
+            format!("{}{}", name.as_bstr(), orient)
+        };
+
+        let bubbles = ultrabubbles(&gfa)
+            .into_iter()
+            .map(|b| (oriented(b.start), oriented(b.end), b.ty))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bubbles,
+            vec![
+                ("h+".to_string(), "j+".to_string(), SnarlType::ChainPair),
+                ("m+".to_string(), "p+".to_string(), SnarlType::ChainPair),
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use crate::biedgedgraph::generate_biedged;
+
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn snarl_boundaries_are_black_endpoints(
+            segments in 1usize..40,
+            links in 0usize..80,
+            seed in any::<u64>(),
+        ) {
+            let graph = generate_biedged(segments, links, seed);
+            prop_assert_eq!(graph.validate(), Ok(()));
+
+            let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+            let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+            let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+            let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+            for snarl in snarl_map.snarls.values() {
+                prop_assert!(graph.is_black_endpoint(snarl.left()));
+                prop_assert!(graph.is_black_endpoint(snarl.right()));
+            }
         }
     }
 }
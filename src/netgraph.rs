@@ -1,7 +1,7 @@
 use rustc_hash::FxHashSet;
 
 use crate::biedgedgraph::BiedgedGraph;
-use crate::cactusgraph::{CactusTree, ChainEdges};
+use crate::cactusgraph::CactusTree;
 use crate::snarls::{Biedged, Node, Snarl, SnarlMap};
 
 #[derive(Clone)]
@@ -30,7 +30,7 @@ impl Color {
 impl NetGraph {
     pub fn contained_snarls(
         &self,
-        cactus_tree: &CactusTree,
+        _cactus_tree: &CactusTree,
         snarl_map: &SnarlMap,
     ) -> FxHashSet<Snarl<()>> {
         let mut res: FxHashSet<Snarl<()>> = FxHashSet::default();
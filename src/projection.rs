@@ -1,23 +1,179 @@
-use crate::biedgedgraph::BiedgedGraph;
+use crate::biedgedgraph::{BiedgedGraph, NamingScheme};
 
 use crate::snarls::Node;
 
-use petgraph::unionfind::UnionFind;
+use bstr::BString;
+use gfa::gfa::name_conversion::NameMap;
 
 use rustc_hash::FxHashMap;
 
+use std::collections::BTreeMap;
+
+/// Disjoint-set forest over the vertex indices of a graph. This
+/// mirrors `petgraph::unionfind::UnionFind`, including which element
+/// becomes the representative on a regular union, but also supports
+/// unions where the caller picks the representative.
+#[derive(Debug, Clone)]
+struct DisjointSets {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+    // The previous parent and rank of each element that was changed,
+    // in order, while logging
+    log: Option<Vec<(usize, usize, u8)>>,
+}
+
+impl DisjointSets {
+    fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+            rank: vec![0; n],
+            log: None,
+        }
+    }
+
+    #[inline]
+    fn set(&mut self, x: usize, parent: usize, rank: u8) {
+        if let Some(log) = self.log.as_mut() {
+            log.push((x, self.parent[x], self.rank[x]));
+        }
+        self.parent[x] = parent;
+        self.rank[x] = rank;
+    }
+
+    #[inline]
+    fn set_parent(&mut self, x: usize, parent: usize) {
+        self.set(x, parent, self.rank[x]);
+    }
+
+    #[inline]
+    fn set_rank(&mut self, x: usize, rank: u8) {
+        self.set(x, self.parent[x], rank);
+    }
+
+    fn find(&self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            x = self.parent[x];
+        }
+        x
+    }
+
+    fn find_mut(&mut self, mut x: usize) -> usize {
+        let mut parent = self.parent[x];
+        while parent != x {
+            let grandparent = self.parent[parent];
+            self.set_parent(x, grandparent);
+            x = parent;
+            parent = grandparent;
+        }
+        x
+    }
+
+    fn union(&mut self, x: usize, y: usize) -> bool {
+        if x == y {
+            return false;
+        }
+
+        let x_rep = self.find_mut(x);
+        let y_rep = self.find_mut(y);
+
+        if x_rep == y_rep {
+            return false;
+        }
+
+        match self.rank[x_rep].cmp(&self.rank[y_rep]) {
+            std::cmp::Ordering::Less => self.set_parent(x_rep, y_rep),
+            std::cmp::Ordering::Greater => self.set_parent(y_rep, x_rep),
+            std::cmp::Ordering::Equal => {
+                self.set_parent(y_rep, x_rep);
+                self.set_rank(x_rep, self.rank[x_rep] + 1);
+            }
+        }
+
+        true
+    }
+
+    /// Merge the sets containing `keep` and `drop`, making `keep`
+    /// the representative of the union.
+    fn union_into(&mut self, keep: usize, drop: usize) -> bool {
+        let keep_rep = self.find_mut(keep);
+        let drop_rep = self.find_mut(drop);
+
+        if keep_rep != keep {
+            self.set_parent(keep_rep, keep);
+            self.set(keep, keep, self.rank[keep_rep]);
+        }
+
+        if keep_rep == drop_rep {
+            return false;
+        }
+
+        self.set_parent(drop_rep, keep);
+
+        // as in a regular union, the rank only grows when merging two
+        // sets of equal rank, but here the larger set may end up below
+        // `keep`, in which case `keep` takes its rank
+        match self.rank[keep].cmp(&self.rank[drop_rep]) {
+            std::cmp::Ordering::Less => {
+                self.set_rank(keep, self.rank[drop_rep])
+            }
+            std::cmp::Ordering::Equal => {
+                self.set_rank(keep, self.rank[keep] + 1)
+            }
+            std::cmp::Ordering::Greater => (),
+        }
+
+        true
+    }
+
+    /// Revert the changes in `log`, newest first.
+    fn undo(&mut self, log: Vec<(usize, usize, u8)>) {
+        for (x, parent, rank) in log.into_iter().rev() {
+            self.parent[x] = parent;
+            self.rank[x] = rank;
+        }
+    }
+
+    fn into_labeling(mut self) -> Vec<usize> {
+        for ix in 0..self.parent.len() {
+            let rep = self.find_mut(ix);
+            self.parent[ix] = rep;
+        }
+        self.parent
+    }
+}
+
 /// Encapsulates a mapping of vertices in an original graph to their
 /// projections in another. Also provides an inverse mapping, so as to
 /// find which vertices were projected into a given vertex.
 #[derive(Clone)]
 pub struct Projection {
     pub size: usize,
-    union_find: UnionFind<usize>,
+    union_find: DisjointSets,
     inverse: Option<FxHashMap<u64, Vec<u64>>>,
 }
 
 pub type InverseProjection = FxHashMap<u64, Vec<u64>>;
 
+/// The changes made to a projection between `Projection::start_log`
+/// and `Projection::take_log`, which `Projection::undo` reverts.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProjectionLog {
+    changes: Vec<(usize, usize, u8)>,
+}
+
+impl ProjectionLog {
+    /// The number of changes to the union-find in the log.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
 impl Projection {
     /// Utility function for use when cloning a graph and its
     /// projection map, with the intention of mutating them. As the
@@ -32,13 +188,35 @@ impl Projection {
         }
     }
 
+    /// Start recording the changes made to the projection, replacing
+    /// any log that was already being recorded.
+    #[inline]
+    pub fn start_log(&mut self) {
+        self.union_find.log = Some(Vec::new());
+    }
+
+    /// Stop recording changes, and return the ones made since
+    /// `start_log` was called.
+    #[inline]
+    pub fn take_log(&mut self) -> ProjectionLog {
+        let changes = self.union_find.log.take().unwrap_or_default();
+        ProjectionLog { changes }
+    }
+
+    /// Revert the changes recorded in `log`. Any changes made after
+    /// them must already have been reverted. Like the other changes
+    /// to the projection, this doesn't update the inverse map.
+    pub fn undo(&mut self, log: ProjectionLog) {
+        self.union_find.undo(log.changes);
+    }
+
     /// Construct a new projection map for a biedged graph. The graph
     /// must have its vertex IDs tightly packed, starting from zero or
     /// one.
     #[inline]
     pub fn new_for_biedged_graph<G>(graph: &BiedgedGraph<G>) -> Self {
         let size = (graph.max_net_vertex.id + 1) as usize;
-        let union_find = UnionFind::new(size);
+        let union_find = DisjointSets::new(size);
         let inverse = None;
         Self {
             size,
@@ -59,6 +237,17 @@ impl Projection {
         Node::from(self.union_find.find_mut(x) as u64)
     }
 
+    /// Find the projection of `x`, along with whether it's the same
+    /// side of its segment as `x`, i.e. both are left-hand or both
+    /// are right-hand vertices. Contracting an edge keeps one of its
+    /// vertices as it is, so a projection is always a vertex of the
+    /// original graph, and its side is given by its ID.
+    #[inline]
+    pub fn find_with_side(&self, x: Node) -> (Node, bool) {
+        let projected = self.find(x);
+        (projected, projected.is_left() == x.is_left())
+    }
+
     #[inline]
     pub fn find_edge(&self, x: Node, y: Node) -> (Node, Node) {
         let x = self.union_find.find(x.id as usize);
@@ -78,9 +267,19 @@ impl Projection {
         self.union_find.union(x.id as usize, y.id as usize)
     }
 
+    /// Merge the projections of `keep` and `drop`, making `keep` the
+    /// vertex they both project to. Returns false if they already
+    /// had the same projection.
+    #[inline]
+    pub fn union_into(&mut self, keep: Node, drop: Node) -> bool {
+        self.union_find
+            .union_into(keep.id as usize, drop.id as usize)
+    }
+
     #[inline]
     pub fn equiv(&self, x: Node, y: Node) -> bool {
-        self.union_find.equiv(x.id as usize, y.id as usize)
+        self.union_find.find(x.id as usize)
+            == self.union_find.find(y.id as usize)
     }
 
     /// Given a pair of vertices, return a corresponding pair with one
@@ -91,9 +290,9 @@ impl Projection {
     pub fn kept_pair(&mut self, x: Node, y: Node) -> (Node, Node) {
         let union = self.union_find.find_mut(x.id as usize) as u64;
         if union == x.id {
-            (union.into(), y.into())
+            (union.into(), y)
         } else {
-            (union.into(), x.into())
+            (union.into(), x)
         }
     }
 
@@ -142,6 +341,14 @@ impl Projection {
         self.inverse.as_ref()
     }
 
+    /// Returns a map from each vertex in the original graph to the
+    /// vertex it projects to.
+    pub fn projection_map(&self) -> BTreeMap<u64, u64> {
+        (0..self.size)
+            .map(|ix| (ix as u64, self.union_find.find(ix) as u64))
+            .collect()
+    }
+
     /// Given a projected vertex, return a slice containing all the
     /// vertex in the original graph that projected to it. Returns
     /// None if the inverse map hasn't been built.
@@ -153,6 +360,66 @@ impl Projection {
     }
 }
 
+/// Inverts a map from vertices to their projections, as produced by
+/// `Projection::projection_map`, into a map from each projected
+/// vertex to the sorted list of vertices that project to it.
+pub fn invert_projection(
+    proj_map: &BTreeMap<u64, u64>,
+) -> BTreeMap<u64, Vec<u64>> {
+    let mut inverse: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+
+    for (&x, &projected) in proj_map.iter() {
+        inverse.entry(projected).or_default().push(x);
+    }
+
+    inverse
+}
+
+/// Follows a map from vertices to vertices they were projected into
+/// until reaching a vertex that maps to itself, or isn't in the map.
+/// Unlike the maps produced by `Projection::projection_map`, where
+/// each vertex maps directly to its final projection, the map may
+/// contain chains of projections, e.g. from a log of contractions.
+///
+/// Panics if the chain starting at `n` runs into a cycle of more than
+/// one vertex, such as {a → b, b → a}, which has no final projection.
+pub fn find_projection(proj_map: &BTreeMap<u64, u64>, mut n: u64) -> u64 {
+    // without a cycle, the chain can't take more steps than there are
+    // vertices in the map
+    for _ in 0..=proj_map.len() {
+        match proj_map.get(&n) {
+            Some(&next) if next != n => n = next,
+            _ => return n,
+        }
+    }
+    panic!("projection map has a cycle through vertex {}", n);
+}
+
+/// Like `find_projection`, but also updates every vertex along the
+/// followed chain to map directly to the final projection, so that
+/// repeated lookups don't have to follow the chain again.
+///
+/// Panics on a cycle, like `find_projection`, without changing the
+/// map.
+pub fn find_projection_compress(
+    proj_map: &mut BTreeMap<u64, u64>,
+    n: u64,
+) -> u64 {
+    let root = find_projection(proj_map, n);
+
+    let mut current = n;
+    while current != root {
+        match proj_map.get_mut(&current) {
+            Some(next) => {
+                current = std::mem::replace(next, root);
+            }
+            None => break,
+        }
+    }
+
+    root
+}
+
 /// Returns the "canonical" representation of the given node ID `id`,
 /// which is the left-hand vertex in the biedged graph.
 #[inline]
@@ -173,7 +440,7 @@ pub fn id_to_black_edge(n: u64) -> (u64, u64) {
 /// and return their black edge.
 #[inline]
 pub fn end_to_black_edge(n: u64) -> (u64, u64) {
-    if n % 2 == 0 {
+    if n & 1 == 0 {
         (n, n + 1)
     } else {
         (n - 1, n)
@@ -183,7 +450,7 @@ pub fn end_to_black_edge(n: u64) -> (u64, u64) {
 /// Given a vertex in a biedged graph, retrieve its opposite vertex.
 #[inline]
 pub fn opposite_vertex(n: u64) -> u64 {
-    if n % 2 == 0 {
+    if n & 1 == 0 {
         n + 1
     } else {
         n - 1
@@ -196,3 +463,60 @@ pub fn opposite_vertex(n: u64) -> u64 {
 pub fn id_from_black_edge(n: u64) -> u64 {
     n / 2
 }
+
+/// Maps the IDs of GFA segments back to their names, as needed to
+/// name the vertices of a biedged graph. Implemented both for the
+/// `NameMap` a GFA was converted with, and for the `NamingScheme` the
+/// segment names were parsed with.
+pub trait SegmentNames {
+    /// Returns the name of the segment with the given ID, or None if
+    /// there is no such segment.
+    fn segment_name(&self, id: usize) -> Option<BString>;
+}
+
+impl SegmentNames for NameMap {
+    fn segment_name(&self, id: usize) -> Option<BString> {
+        self.inverse_map_name(id).map(BString::from)
+    }
+}
+
+impl SegmentNames for NamingScheme {
+    fn segment_name(&self, id: usize) -> Option<BString> {
+        Some(BString::from(NamingScheme::segment_name(self, id)))
+    }
+}
+
+/// Returns the name of the GFA segment the provided biedged vertex
+/// belongs to, using the name map or naming scheme the GFA was
+/// converted with. The right-hand side of a segment is suffixed with
+/// `_`.
+pub fn projected_node_name<N: SegmentNames + ?Sized>(
+    names: &N,
+    n: Node,
+) -> Option<BString> {
+    let mut name = names.segment_name(n.to_gfa_id() as usize)?;
+    if n.is_right() {
+        name.push(b'_');
+    }
+    Some(name)
+}
+
+/// Given the ID of a segment in the GFA used to build the biedged
+/// graph, find the vertices the two ends of the segment were
+/// projected to in `proj_map`, as with `find_projection`, and return
+/// their names, as produced by `projected_node_name`.
+///
+/// Returns `None` if the segment has no name, or if its ID is too
+/// large to have a pair of vertices.
+pub fn projected_segment_ends<N: SegmentNames + ?Sized>(
+    proj_map: &BTreeMap<u64, u64>,
+    name_map: &N,
+    gfa_id: u64,
+) -> Option<(BString, BString)> {
+    let (left, right) = Node::try_from_gfa_id(gfa_id)?;
+    let left = Node::new(find_projection(proj_map, left.id));
+    let right = Node::new(find_projection(proj_map, right.id));
+    let left = projected_node_name(name_map, left)?;
+    let right = projected_node_name(name_map, right)?;
+    Some((left, right))
+}
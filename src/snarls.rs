@@ -1,7 +1,16 @@
-use log::{debug, trace};
-
 use rustc_hash::{FxHashMap, FxHashSet};
 
+use gfa::gfa::name_conversion::NameMap;
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::biedgedgraph::{BiedgedGraph, BiedgedWeight};
+use crate::projection::projected_node_name;
+
 #[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Biedged {}
 #[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -16,11 +25,24 @@ impl GraphType for Cactus {}
 impl GraphType for Bridge {}
 
 /// A node index for a biedged graph of the specified type
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Node {
     pub id: u64,
 }
 
+impl std::fmt::Debug for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let side = if self.is_left() { 'L' } else { 'R' };
+        write!(f, "Node({}{})", self.to_gfa_id(), side)
+    }
+}
+
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
 impl From<u64> for Node {
     #[inline]
     fn from(id: u64) -> Self {
@@ -49,6 +71,16 @@ impl Node {
         (Self::new(left), Self::new(right))
     }
 
+    /// Like `from_gfa_id`, but returns None if the IDs of the black
+    /// edge nodes would overflow.
+    #[inline]
+    pub fn try_from_gfa_id(id: u64) -> Option<(Self, Self)> {
+        let left = id.checked_mul(2)?;
+        let right = left + 1;
+
+        Some((Self::new(left), Self::new(right)))
+    }
+
     /// Derive the original GFA ID for the provided black edge node ID
     #[inline]
     pub fn to_gfa_id(&self) -> u64 {
@@ -89,12 +121,57 @@ impl Node {
     pub fn is_right(&self) -> bool {
         self.id & 1 != 0
     }
+
+    /// Returns true if this node's ID lies in the inclusive range
+    /// spanned by the two provided nodes, in either order
+    #[inline]
+    pub fn between(&self, a: Node, b: Node) -> bool {
+        let lo = a.min(b);
+        let hi = a.max(b);
+        lo <= *self && *self <= hi
+    }
+
+    /// Return the inclusive range of node IDs covered by the black
+    /// edge defined by this node
+    #[inline]
+    pub fn black_edge_range(&self) -> (u64, u64) {
+        let (left, right) = self.black_edge();
+        (left.id, right.id)
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum SnarlType {
     ChainPair,
     BridgePair,
+    /// A snarl without any contained black edges. Snarls are always
+    /// stored as chain or bridge pairs; this is only produced by
+    /// `SnarlMap::effective_type`.
+    Trivial,
+}
+
+/// Classification of a vertex by the snarls it's a boundary of, as
+/// produced by `SnarlMap::node_role`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NodeRole {
+    /// A boundary of at least one chain pair.
+    ChainBoundary,
+    /// A boundary of bridge pairs only.
+    BridgeBoundary,
+    /// Not a boundary of any snarl.
+    Interior,
+}
+
+/// Classification of a snarl by the paths through its interior, as
+/// produced by `SnarlMap::complexity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SnarlComplexity {
+    /// At most two traversals between the boundaries, e.g. a SNP or
+    /// an indel.
+    Simple,
+    /// More than two traversals, so there are further branch points
+    /// inside the snarl.
+    Complex,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -105,6 +182,29 @@ pub struct Snarl<T: Copy + Eq + Ord + std::hash::Hash> {
     data: T,
 }
 
+impl<T> std::fmt::Debug for Snarl<T>
+where
+    T: Copy + Eq + Ord + std::hash::Hash + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Snarl")
+            .field("left", &self.left)
+            .field("right", &self.right)
+            .field("ty", &self.ty)
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+impl<T> std::fmt::Display for Snarl<T>
+where
+    T: Copy + Eq + Ord + std::hash::Hash,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}({}, {})", self.ty, self.left, self.right)
+    }
+}
+
 impl<T> Snarl<T>
 where
     T: Copy + Eq + Ord + std::hash::Hash,
@@ -167,6 +267,23 @@ where
         self.data
     }
 
+    /// Returns the boundaries and type of the snarl, which identify
+    /// it regardless of the data it carries or its rank in a map.
+    pub fn boundary_key(&self) -> (Node, Node, SnarlType) {
+        (self.left, self.right, self.ty)
+    }
+
+    /// Returns the GFA segment IDs of the boundaries, each paired
+    /// with true if the segment is traversed in reverse when walking
+    /// through the snarl from `left` to `right`. The boundaries are
+    /// still ordered by ID, but the orientations don't depend on
+    /// which side of a segment has the lower ID.
+    pub fn oriented_boundaries(&self) -> ((u64, bool), (u64, bool)) {
+        let start = (self.left.to_gfa_id(), self.left.is_left());
+        let end = (self.right.to_gfa_id(), self.right.is_right());
+        (start, end)
+    }
+
     pub fn chain_pair_with(x: Node, y: Node, data: T) -> Self {
         let left = x.min(y);
         let right = x.max(y);
@@ -205,6 +322,123 @@ where
     }
 }
 
+/// The reason a snarl was rejected by `SnarlMap::try_insert`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnarlConflict {
+    /// A snarl with the same boundaries already exists at this rank.
+    Duplicate(usize),
+    /// The node is already a boundary of the snarl at rank `ix`,
+    /// with a different partner.
+    Boundary { node: Node, ix: usize },
+}
+
+impl std::fmt::Display for SnarlConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnarlConflict::Duplicate(ix) => {
+                write!(f, "snarl already exists with rank {}", ix)
+            }
+            SnarlConflict::Boundary { node, ix } => write!(
+                f,
+                "node {} is already a boundary of snarl {}",
+                node.id, ix
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnarlConflict {}
+
+/// An inconsistency between a snarl map and the biedged graph it's
+/// checked against, as reported by `SnarlMap::audit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SnarlAuditError {
+    /// The boundary `node` of the snarl at rank `ix` is not a vertex
+    /// of the graph.
+    DanglingBoundary { ix: usize, node: Node },
+    /// The snarl at rank `ix` has a contained edge marked for the
+    /// black edge at `edge`, which doesn't exist in the graph.
+    MissingContainedEdge { ix: usize, edge: Node },
+}
+
+impl std::fmt::Display for SnarlAuditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnarlAuditError::DanglingBoundary { ix, node } => write!(
+                f,
+                "boundary {} of snarl {} is not in the graph",
+                node.id, ix
+            ),
+            SnarlAuditError::MissingContainedEdge { ix, edge } => write!(
+                f,
+                "contained black edge {} of snarl {} is not in the graph",
+                edge.id, ix
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnarlAuditError {}
+
+/// The set of black bridge edges contained in a snarl. Each edge is
+/// identified by its left-hand vertex, and can be looked up using
+/// either of its vertices.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ContainedEdges {
+    edges: FxHashSet<Node>,
+}
+
+impl ContainedEdges {
+    /// Returns true if the black edge with `bridge` as one of its
+    /// vertices is contained.
+    #[inline]
+    pub fn contains(&self, bridge: Node) -> bool {
+        self.edges.contains(&bridge.left())
+    }
+
+    /// Iterates the contained black edges, by their left-hand
+    /// vertices.
+    pub fn iter(&self) -> impl Iterator<Item = Node> + '_ {
+        self.edges.iter().copied()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    /// Mark the black edge with `bridge` as one of its vertices as
+    /// contained. Returns false if it already was.
+    pub fn insert(&mut self, bridge: Node) -> bool {
+        self.edges.insert(bridge.left())
+    }
+
+    /// Mark the black edge with `bridge` as one of its vertices as
+    /// not contained. Returns false if it wasn't contained.
+    pub fn remove(&mut self, bridge: Node) -> bool {
+        self.edges.remove(&bridge.left())
+    }
+}
+
+impl std::iter::FromIterator<Node> for ContainedEdges {
+    fn from_iter<I: IntoIterator<Item = Node>>(iter: I) -> Self {
+        let mut contained = Self::default();
+        contained.extend(iter);
+        contained
+    }
+}
+
+impl Extend<Node> for ContainedEdges {
+    fn extend<I: IntoIterator<Item = Node>>(&mut self, iter: I) {
+        self.edges.extend(iter.into_iter().map(|n| n.left()));
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct SnarlMap {
     // Snarls indexed by left boundary
@@ -215,8 +449,44 @@ pub struct SnarlMap {
     // Snarls by rank
     pub snarls: FxHashMap<usize, Snarl<()>>,
 
-    // Map of contained/not contained black edges for each snarl by rank
-    pub snarl_contains: FxHashMap<usize, FxHashMap<Node, bool>>,
+    // Contained black edges for each snarl by rank
+    pub snarl_contains: FxHashMap<usize, ContainedEdges>,
+
+    // Start and end offsets on the reference path for each snarl by
+    // rank, set by `annotate_reference`
+    pub ref_coords: FxHashMap<usize, (usize, usize)>,
+
+    // Rank to use for the next inserted snarl
+    next_rank: usize,
+
+    // Snarls with fewer contained black edges are dropped by `finalize`
+    min_contained: usize,
+}
+
+/// Configures a `SnarlMap` before any snarls are added to it.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SnarlMapBuilder {
+    min_contained: usize,
+}
+
+impl SnarlMapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop the snarls that contain fewer than `n` black edges when
+    /// the map is finalized.
+    pub fn min_contained(mut self, n: usize) -> Self {
+        self.min_contained = n;
+        self
+    }
+
+    pub fn build(self) -> SnarlMap {
+        SnarlMap {
+            min_contained: self.min_contained,
+            ..SnarlMap::default()
+        }
+    }
 }
 
 pub struct SnarlTree {
@@ -242,7 +512,7 @@ impl SnarlTree {
 
                     let snarl = self.map.snarls.get(ix)?;
                     if snarl.is_chain_pair() {
-                        Some(snarl.clone())
+                        Some(*snarl)
                     } else {
                         None
                     }
@@ -255,6 +525,189 @@ impl SnarlTree {
         res
     }
 
+    /// Returns the nesting depth of the snarl with the given rank,
+    /// i.e. the number of other snarls in the tree that contain it,
+    /// or None if there's no such snarl.
+    pub fn depth(&self, snarl_ix: usize) -> Option<usize> {
+        if !self.map.snarls.contains_key(&snarl_ix) {
+            return None;
+        }
+
+        let depth = self
+            .tree
+            .iter()
+            .filter(|(&parent, children)| {
+                parent != snarl_ix && children.contains(&snarl_ix)
+            })
+            .count();
+
+        Some(depth)
+    }
+
+    /// Returns a map from nesting depth, as defined by `depth`, to
+    /// the number of snarls at that depth.
+    pub fn depth_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut depths: FxHashMap<usize, usize> =
+            self.map.snarls.keys().map(|&ix| (ix, 0)).collect();
+
+        for (&parent, children) in self.tree.iter() {
+            for &child in children.iter() {
+                if child != parent {
+                    if let Some(depth) = depths.get_mut(&child) {
+                        *depth += 1;
+                    }
+                }
+            }
+        }
+
+        let mut histogram = BTreeMap::new();
+
+        for depth in depths.into_values() {
+            *histogram.entry(depth).or_default() += 1;
+        }
+
+        histogram
+    }
+
+    /// Returns the ranks of the snarls containing the snarl with the
+    /// given rank, including the snarl itself.
+    fn ancestors(&self, snarl_ix: usize) -> impl Iterator<Item = usize> + '_ {
+        let containing =
+            self.tree.iter().filter_map(move |(&parent, children)| {
+                if parent != snarl_ix && children.contains(&snarl_ix) {
+                    Some(parent)
+                } else {
+                    None
+                }
+            });
+
+        std::iter::once(snarl_ix).chain(containing)
+    }
+
+    /// Returns the greatest nesting depth, as defined by `depth`, of
+    /// any snarl in the tree.
+    pub fn max_depth(&self) -> usize {
+        self.depth_histogram()
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Checks that every snarl in the tree is nested fewer than
+    /// `limit` levels deep, returning the greatest depth as the error
+    /// otherwise.
+    pub fn assert_depth_below(&self, limit: usize) -> Result<(), usize> {
+        let max_depth = self.max_depth();
+        if max_depth < limit {
+            Ok(())
+        } else {
+            Err(max_depth)
+        }
+    }
+
+    /// Returns true if the snarl with rank `inner` is nested inside
+    /// the snarl with rank `outer`. A snarl does not contain itself.
+    pub fn contains_snarl(&self, outer: usize, inner: usize) -> bool {
+        outer != inner
+            && self
+                .tree
+                .get(&outer)
+                .is_some_and(|children| children.contains(&inner))
+    }
+
+    /// Returns the rank of the deepest snarl containing both of the
+    /// given snarls, where a snarl counts as containing itself.
+    /// Returns None if either snarl doesn't exist, or if they're in
+    /// different trees of the forest.
+    pub fn lca(&self, a: usize, b: usize) -> Option<usize> {
+        if !self.map.snarls.contains_key(&a)
+            || !self.map.snarls.contains_key(&b)
+        {
+            return None;
+        }
+
+        let b_ancestors = self.ancestors(b).collect::<FxHashSet<_>>();
+
+        self.ancestors(a)
+            .filter(|ix| b_ancestors.contains(ix))
+            .filter_map(|ix| Some((self.depth(ix)?, ix)))
+            .max()
+            .map(|(_, ix)| ix)
+    }
+
+    /// Returns the ranks of the snarls directly nested in the snarl
+    /// with the given rank, i.e. the ones it contains that aren't
+    /// also contained in another of its children, in ascending order.
+    fn direct_children(&self, snarl_ix: usize) -> Vec<usize> {
+        let children = match self.tree.get(&snarl_ix) {
+            Some(children) => children,
+            None => return Vec::new(),
+        };
+
+        let mut direct = children
+            .iter()
+            .copied()
+            .filter(|&child| {
+                child != snarl_ix
+                    && !children.iter().any(|&other| {
+                        other != child
+                            && other != snarl_ix
+                            && self.contains_snarl(other, child)
+                            && !self.contains_snarl(child, other)
+                    })
+            })
+            .collect::<Vec<_>>();
+        direct.sort_unstable();
+        direct
+    }
+
+    /// Iterates the ranks of the snarls in the subtree rooted at the
+    /// snarl with rank `root`, in depth-first preorder, visiting the
+    /// children of each snarl in ascending order of rank. Produces
+    /// nothing if there's no such snarl.
+    pub fn dfs(&self, root: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut stack = Vec::new();
+        if self.map.snarls.contains_key(&root) {
+            stack.push(root);
+        }
+
+        let mut visited: FxHashSet<usize> = FxHashSet::default();
+
+        std::iter::from_fn(move || {
+            while let Some(ix) = stack.pop() {
+                if visited.insert(ix) {
+                    stack.extend(self.direct_children(ix).into_iter().rev());
+                    return Some(ix);
+                }
+            }
+            None
+        })
+    }
+
+    /// Iterates the ranks of the snarls in the subtree rooted at the
+    /// snarl with rank `root`, in breadth-first order, visiting the
+    /// children of each snarl in ascending order of rank. Produces
+    /// nothing if there's no such snarl.
+    pub fn bfs(&self, root: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut queue = std::collections::VecDeque::new();
+        if self.map.snarls.contains_key(&root) {
+            queue.push_back(root);
+        }
+
+        let mut visited: FxHashSet<usize> = FxHashSet::default();
+
+        std::iter::from_fn(move || {
+            while let Some(ix) = queue.pop_front() {
+                if visited.insert(ix) {
+                    queue.extend(self.direct_children(ix));
+                    return Some(ix);
+                }
+            }
+            None
+        })
+    }
+
     pub fn contained(
         &self,
         snarl_ix: usize,
@@ -276,22 +729,7 @@ impl SnarlTree {
         let mut contains_by_size: Vec<(usize, FxHashSet<Node>)> = snarl_map
             .snarl_contains
             .iter()
-            .map(|(&k, v)| {
-                let bridges = v
-                    .iter()
-                    .filter_map(
-                        |(&b, &contains)| {
-                            if contains {
-                                Some(b)
-                            } else {
-                                None
-                            }
-                        },
-                    )
-                    .collect::<FxHashSet<_>>();
-
-                (k, bridges)
-            })
+            .map(|(&k, v)| (k, v.iter().collect::<FxHashSet<_>>()))
             .collect();
 
         contains_by_size.sort_by_key(|(_, bridges)| bridges.len());
@@ -306,10 +744,8 @@ impl SnarlTree {
             Default::default();
 
         for (&snarl_ix, contained) in snarl_map.snarl_contains.iter() {
-            for (&bridge, &contains) in contained.iter() {
-                if contains {
-                    bridge_snarls.entry(bridge).or_default().insert(snarl_ix);
-                }
+            for bridge in contained.iter() {
+                bridge_snarls.entry(bridge).or_default().insert(snarl_ix);
             }
         }
 
@@ -341,6 +777,8 @@ impl SnarlTree {
 }
 
 pub struct SnarlMapIter<'a> {
+    x: Node,
+
     lefts: Option<std::slice::Iter<'a, usize>>,
     rights: Option<std::slice::Iter<'a, usize>>,
 
@@ -353,6 +791,8 @@ impl<'a> SnarlMapIter<'a> {
         let rights = snarl_map.rights.get(&x).map(|rights| rights.iter());
 
         Self {
+            x,
+
             lefts,
             rights,
 
@@ -365,39 +805,98 @@ impl<'a> Iterator for SnarlMapIter<'a> {
     type Item = Snarl<()>;
 
     fn next(&mut self) -> Option<Snarl<()>> {
-        if self.lefts.is_none() && self.rights.is_none() {
-            return None;
-        }
-
         if let Some(lefts) = self.lefts.as_mut() {
-            if let Some(ix) = lefts.next() {
-                let v = *self.snarls.get(ix)?;
-                return Some(v);
-            } else {
-                self.lefts = None;
+            for ix in lefts {
+                if let Some(snarl) = self.snarls.get(ix) {
+                    return Some(*snarl);
+                }
             }
+            self.lefts = None;
         }
 
         if let Some(rights) = self.rights.as_mut() {
-            if let Some(ix) = rights.next() {
-                let v = *self.snarls.get(ix)?;
-                return Some(v);
-            } else {
-                self.rights = None;
+            for ix in rights {
+                // snarls with `x` as both boundaries have already
+                // been produced from the lefts
+                match self.snarls.get(ix) {
+                    Some(snarl) if snarl.left() != self.x => {
+                        return Some(*snarl)
+                    }
+                    _ => (),
+                }
             }
+            self.rights = None;
         }
 
         None
     }
 }
 
+impl<'a> std::iter::FusedIterator for SnarlMapIter<'a> {}
+
+/// A finalized, read-only snarl map, as produced by
+/// `SnarlMap::freeze`. It only supports queries, so it can be shared
+/// between threads, e.g. in an `Arc`, without any locking.
+pub struct SharedSnarlMap {
+    map: SnarlMap,
+    // Snarls containing each black bridge edge, by left-hand vertex,
+    // sorted by their boundaries
+    containing: FxHashMap<Node, Vec<Snarl<()>>>,
+}
+
+impl SharedSnarlMap {
+    pub fn get(&self, x: Node, y: Node) -> Option<Snarl<()>> {
+        self.map.get(x, y)
+    }
+
+    pub fn with_boundary(&self, x: Node) -> SnarlMapIter<'_> {
+        self.map.with_boundary(x)
+    }
+
+    /// Returns the snarls that contain the black bridge edge with `x`
+    /// as one of its vertices, sorted by their boundaries.
+    pub fn containing_snarls(&self, x: Node) -> &[Snarl<()>] {
+        self.containing
+            .get(&x.left())
+            .map(|snarls| snarls.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.snarls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.snarls.is_empty()
+    }
+}
+
+/// Inserts the snarls like repeated calls to `SnarlMap::insert`, but
+/// checks for duplicates against a set of the boundaries in the map,
+/// built once, rather than looking up each snarl's boundaries.
+impl Extend<Snarl<()>> for SnarlMap {
+    fn extend<I: IntoIterator<Item = Snarl<()>>>(&mut self, iter: I) {
+        let mut boundaries = self
+            .snarls
+            .values()
+            .map(|snarl| (snarl.left(), snarl.right()))
+            .collect::<FxHashSet<_>>();
+
+        for snarl in iter {
+            if boundaries.insert((snarl.left(), snarl.right())) {
+                self.insert_unchecked(snarl);
+            }
+        }
+    }
+}
+
 impl SnarlMap {
     pub fn filter_snarls(&mut self) {
         let mut to_delete: Vec<usize> = Vec::new();
         let mut to_keep: FxHashSet<usize> = Default::default();
 
         for (snarl_ix, bridges) in self.snarl_contains.iter() {
-            if bridges.iter().any(|(_, &b)| b) {
+            if !bridges.is_empty() {
                 to_delete.push(*snarl_ix);
             } else {
                 to_keep.insert(*snarl_ix);
@@ -431,18 +930,190 @@ impl SnarlMap {
             return;
         }
 
-        let ix = self.snarls.len();
+        self.insert_unchecked(snarl);
+    }
+
+    /// Strict version of `insert`, which fails if the snarl already
+    /// exists, or if either of its boundaries is already the boundary
+    /// of another snarl. Returns the rank of the inserted snarl.
+    pub fn try_insert(
+        &mut self,
+        snarl: Snarl<()>,
+    ) -> Result<usize, SnarlConflict> {
+        if let Some(ix) = self.get_snarl_ix(snarl.left, snarl.right) {
+            return Err(SnarlConflict::Duplicate(ix));
+        }
+
+        for &node in [snarl.left(), snarl.right()].iter() {
+            let existing = self
+                .lefts
+                .get(&node)
+                .into_iter()
+                .chain(self.rights.get(&node))
+                .flatten()
+                .next();
+
+            if let Some(&ix) = existing {
+                return Err(SnarlConflict::Boundary { node, ix });
+            }
+        }
+
+        Ok(self.insert_unchecked(snarl))
+    }
+
+    fn insert_unchecked(&mut self, snarl: Snarl<()>) -> usize {
+        let ix = self.next_rank.max(self.snarls.len());
+        self.next_rank = ix + 1;
 
         self.snarls.insert(ix, snarl);
 
         self.lefts.entry(snarl.left()).or_default().push(ix);
         self.rights.entry(snarl.right()).or_default().push(ix);
+
+        ix
+    }
+
+    /// Remove the snarl with the given boundaries, if it exists. The
+    /// ranks of the remaining snarls are left unchanged, so the ranks
+    /// in the map may be sparse afterward; use `compact` to renumber
+    /// them.
+    pub fn remove(&mut self, x: Node, y: Node) -> Option<Snarl<()>> {
+        let snarl_ix = self.get_snarl_ix(x, y)?;
+        let snarl = self.snarls.remove(&snarl_ix)?;
+
+        self.snarl_contains.remove(&snarl_ix);
+        self.ref_coords.remove(&snarl_ix);
+
+        for (node, ixs) in [
+            (snarl.left(), &mut self.lefts),
+            (snarl.right(), &mut self.rights),
+        ]
+        .iter_mut()
+        {
+            if let Some(node_ixs) = ixs.get_mut(node) {
+                node_ixs.retain(|&ix| ix != snarl_ix);
+                if node_ixs.is_empty() {
+                    ixs.remove(node);
+                }
+            }
+        }
+
+        Some(snarl)
+    }
+
+    /// Remove the snarls that contain fewer black edges than the
+    /// minimum the map was built with, once all the snarls have been
+    /// marked. The remaining snarls keep their ranks. Returns the
+    /// number of snarls that were removed.
+    pub fn finalize(&mut self) -> usize {
+        let mut small = self
+            .snarls
+            .iter()
+            .filter(|(&ix, _)| self.contained_count_ix(ix) < self.min_contained)
+            .map(|(_, snarl)| *snarl)
+            .collect::<Vec<_>>();
+        small.sort();
+
+        for snarl in small.iter() {
+            self.remove(snarl.left(), snarl.right());
+        }
+
+        small.len()
+    }
+
+    /// Add the snarls in `other` to this map, giving them new ranks
+    /// after the ones already in use. Snarls with boundaries that
+    /// already exist in this map are not duplicated; their contained
+    /// edges are added to those of the existing snarl, with an edge
+    /// being contained if either map marks it as such.
+    pub fn merge(&mut self, mut other: SnarlMap) {
+        let mut other_ranks = other.snarls.keys().copied().collect::<Vec<_>>();
+        other_ranks.sort_unstable();
+
+        for other_ix in other_ranks {
+            let snarl = other.snarls[&other_ix];
+
+            let ix = match self.get_snarl_ix(snarl.left(), snarl.right()) {
+                Some(ix) => ix,
+                None => self.insert_unchecked(snarl),
+            };
+
+            if let Some(other_contains) = other.snarl_contains.remove(&other_ix)
+            {
+                let contains = self.snarl_contains.entry(ix).or_default();
+                contains.extend(other_contains.iter());
+            }
+
+            if let Some(coords) = other.ref_coords.remove(&other_ix) {
+                self.ref_coords.entry(ix).or_insert(coords);
+            }
+        }
+    }
+
+    /// Renumber the snarls in the map so that their ranks cover the
+    /// range `0..len`, preserving their relative order. Returns the
+    /// map from old to new ranks.
+    pub fn compact(&mut self) -> FxHashMap<usize, usize> {
+        let mut old_ranks = self.snarls.keys().copied().collect::<Vec<_>>();
+        old_ranks.sort_unstable();
+
+        let rank_map: FxHashMap<usize, usize> = old_ranks
+            .into_iter()
+            .enumerate()
+            .map(|(new, old)| (old, new))
+            .collect();
+
+        self.snarls = self
+            .snarls
+            .drain()
+            .map(|(ix, snarl)| (rank_map[&ix], snarl))
+            .collect();
+
+        self.snarl_contains = self
+            .snarl_contains
+            .drain()
+            .filter_map(|(ix, contains)| Some((*rank_map.get(&ix)?, contains)))
+            .collect();
+
+        self.ref_coords = self
+            .ref_coords
+            .drain()
+            .filter_map(|(ix, coords)| Some((*rank_map.get(&ix)?, coords)))
+            .collect();
+
+        for ixs in self.lefts.values_mut().chain(self.rights.values_mut()) {
+            for ix in ixs.iter_mut() {
+                *ix = rank_map[ix];
+            }
+        }
+
+        self.next_rank = self.snarls.len();
+
+        rank_map
     }
 
     pub fn with_boundary(&self, x: Node) -> SnarlMapIter<'_> {
         SnarlMapIter::new(self, x)
     }
 
+    /// Iterates the snarls that have `x` as their left boundary.
+    pub fn snarls_with_left(
+        &self,
+        x: Node,
+    ) -> impl Iterator<Item = Snarl<()>> + '_ {
+        let lefts = self.lefts.get(&x).into_iter().flatten();
+        lefts.filter_map(move |ix| self.snarls.get(ix).copied())
+    }
+
+    /// Iterates the snarls that have `x` as their right boundary.
+    pub fn snarls_with_right(
+        &self,
+        x: Node,
+    ) -> impl Iterator<Item = Snarl<()>> + '_ {
+        let rights = self.rights.get(&x).into_iter().flatten();
+        rights.filter_map(move |ix| self.snarls.get(ix).copied())
+    }
+
     pub fn get_snarl_ix(&self, x: Node, y: Node) -> Option<usize> {
         let left = x.min(y);
         let right = x.max(y);
@@ -473,34 +1144,453 @@ impl SnarlMap {
         Some(*snarl)
     }
 
-    pub fn mark_snarl(
-        &mut self,
-        x: Node,
-        y: Node,
-        bridge: Node,
-        contains: bool,
-    ) -> Option<()> {
-        let snarl_ix = self.get_snarl_ix(x, y)?;
+    /// Finalize the map, producing a read-only version of it that can
+    /// be queried from multiple threads at once.
+    pub fn freeze(mut self) -> SharedSnarlMap {
+        self.lefts.shrink_to_fit();
+        self.rights.shrink_to_fit();
+        self.snarls.shrink_to_fit();
+        self.snarl_contains.shrink_to_fit();
+        self.ref_coords.shrink_to_fit();
+
+        let containing = self
+            .invert_contains()
+            .into_iter()
+            .map(|(bridge, snarls)| {
+                let mut snarls = snarls.into_iter().collect::<Vec<_>>();
+                snarls.sort_unstable();
+                (bridge, snarls)
+            })
+            .collect();
 
-        let snarl_contains = self.snarl_contains.entry(snarl_ix).or_default();
+        SharedSnarlMap {
+            map: self,
+            containing,
+        }
+    }
 
-        let bridge_canonical = bridge.left();
+    /// Returns the snarls in the map sorted by their boundaries and
+    /// type, independent of their ranks and the order they were
+    /// inserted in.
+    pub fn to_vec(&self) -> Vec<Snarl<()>> {
+        let mut snarls = self.snarls.values().copied().collect::<Vec<_>>();
+        snarls.sort_unstable_by_key(|s| s.boundary_key());
+        snarls
+    }
+
+    /// Replace each run of consecutive trivial snarls along `chain`
+    /// with a single chain pair spanning the run. `chain` lists the
+    /// vertices along the chain, as in `snarls_in_chain_order`, and
+    /// two snarls are consecutive if the second starts where the
+    /// first ends, or on the other side of the segment the first
+    /// ends at. The black edges of the segments between the snarls
+    /// of a run are marked as contained in the new snarl. Snarls
+    /// with a boundary that isn't on the chain are left as they are.
+    pub fn collapse_trivial_chains(&mut self, chain: &[Node]) {
+        let position: FxHashMap<Node, usize> =
+            chain.iter().enumerate().map(|(ix, &n)| (n, ix)).collect();
+
+        let trivial = self
+            .snarls_in_chain_order(chain)
+            .into_iter()
+            .filter(|snarl| {
+                position.contains_key(&snarl.right())
+                    && self.is_trivial(snarl.left(), snarl.right())
+                        == Some(true)
+            })
+            .collect::<Vec<_>>();
 
-        snarl_contains.insert(bridge_canonical, contains);
+        let mut runs: Vec<Vec<Snarl<()>>> = Vec::new();
 
-        Some(())
+        for snarl in trivial {
+            let start = position[&snarl.left()];
+
+            let extends_run = runs.last().is_some_and(|run| {
+                let end = position[&run.last().unwrap().right()];
+                start == end || start == end + 1
+            });
+
+            match runs.last_mut() {
+                Some(run) if extends_run => run.push(snarl),
+                _ => runs.push(vec![snarl]),
+            }
+        }
+
+        for run in runs.into_iter().filter(|run| run.len() > 1) {
+            for snarl in run.iter() {
+                self.remove(snarl.left(), snarl.right());
+            }
+
+            let x = run[0].left();
+            let y = run[run.len() - 1].right();
+            self.insert(Snarl::chain_pair(x, y));
+
+            for pair in run.windows(2) {
+                let end = pair[0].right();
+                if pair[1].left() == end.opposite() {
+                    self.mark_snarl(x, y, end.left(), true);
+                }
+            }
+        }
+    }
+
+    /// Returns the snarls whose boundaries span a range of vertex IDs
+    /// that intersects the range from `a` to `b`, inclusive, sorted
+    /// by their boundaries.
+    pub fn overlapping(&self, a: Node, b: Node) -> Vec<Snarl<()>> {
+        let (start, end) = (a.min(b), a.max(b));
+
+        let mut snarls = self
+            .snarls
+            .values()
+            .filter(|snarl| snarl.left() <= end && snarl.right() >= start)
+            .copied()
+            .collect::<Vec<_>>();
+
+        snarls.sort();
+        snarls
     }
 
-    pub fn snarl_contains(
+    /// Returns the innermost snarl containing the gray edge of the
+    /// GFA link from segment `from` to segment `to`, each given as its
+    /// ID and true if the link uses it in reverse. The gray edge is
+    /// inside the snarls that have either of its vertices as a
+    /// boundary, and the snarls that contain the black bridge edge at
+    /// either of its vertices. Of those, the innermost is the one that
+    /// contains the fewest black bridge edges, with ties broken by the
+    /// order of the snarls. The link itself isn't looked up, so this
+    /// doesn't need the graph.
+    pub fn snarl_of_link(
         &self,
+        (from, from_rev): (u64, bool),
+        (to, to_rev): (u64, bool),
+    ) -> Option<Snarl<()>> {
+        let (from_left, from_right) = Node::from_gfa_id(from);
+        let (to_left, to_right) = Node::from_gfa_id(to);
+
+        // the link leaves `from` on the side it ends at, and enters
+        // `to` on the side it starts at
+        let a = if from_rev { from_left } else { from_right };
+        let b = if to_rev { to_right } else { to_left };
+
+        let containing = self
+            .snarl_contains
+            .iter()
+            .filter(|(_, contained)| {
+                contained.contains(a) || contained.contains(b)
+            })
+            .filter_map(|(ix, _)| self.snarls.get(ix).copied());
+
+        self.with_boundary(a)
+            .chain(self.with_boundary(b))
+            .chain(containing)
+            .min_by_key(|&snarl| {
+                let contained =
+                    self.contained_count(snarl.left(), snarl.right());
+                (contained, snarl)
+            })
+    }
+
+    /// Returns the snarls whose left boundary is in `chain`, ordered
+    /// by the position of their left boundary in the chain, and then
+    /// by the position of their right boundary, with right boundaries
+    /// that aren't in the chain placed last. Snarls with a left
+    /// boundary outside the chain are not included.
+    pub fn snarls_in_chain_order(&self, chain: &[Node]) -> Vec<Snarl<()>> {
+        let position: FxHashMap<Node, usize> =
+            chain.iter().enumerate().map(|(ix, &n)| (n, ix)).collect();
+
+        let mut snarls = self
+            .snarls
+            .values()
+            .filter_map(|snarl| {
+                let left = *position.get(&snarl.left())?;
+                let right =
+                    position.get(&snarl.right()).copied().unwrap_or(usize::MAX);
+                Some(((left, right), *snarl))
+            })
+            .collect::<Vec<_>>();
+
+        snarls.sort();
+
+        snarls.into_iter().map(|(_, snarl)| snarl).collect()
+    }
+
+    /// Returns the snarl with the given rank, if it exists.
+    pub fn snarl_by_rank(&self, ix: usize) -> Option<Snarl<()>> {
+        self.snarls.get(&ix).copied()
+    }
+
+    /// Returns the left and right boundaries of the snarl with the
+    /// given rank, if it exists.
+    pub fn boundaries(&self, ix: usize) -> Option<(Node, Node)> {
+        let snarl = self.snarls.get(&ix)?;
+        Some((snarl.left(), snarl.right()))
+    }
+
+    pub fn mark_snarl(
+        &mut self,
         x: Node,
         y: Node,
-    ) -> Option<&FxHashMap<Node, bool>> {
+        bridge: Node,
+        contains: bool,
+    ) -> Option<()> {
+        let snarl_ix = self.get_snarl_ix(x, y)?;
+
+        let snarl_contains = self.snarl_contains.entry(snarl_ix).or_default();
+
+        if contains {
+            snarl_contains.insert(bridge);
+        } else {
+            snarl_contains.remove(bridge);
+        }
+
+        Some(())
+    }
+
+    pub fn snarl_contains(&self, x: Node, y: Node) -> Option<&ContainedEdges> {
         let snarl_ix = self.get_snarl_ix(x, y)?;
 
         self.snarl_contains.get(&snarl_ix)
     }
 
+    /// Returns true if the snarl with the given boundaries doesn't
+    /// contain any black bridge edges, or None if the snarl doesn't
+    /// exist.
+    pub fn is_trivial(&self, x: Node, y: Node) -> Option<bool> {
+        let snarl_ix = self.get_snarl_ix(x, y)?;
+        Some(self.is_trivial_ix(snarl_ix))
+    }
+
+    fn is_trivial_ix(&self, snarl_ix: usize) -> bool {
+        self.contained_count_ix(snarl_ix) == 0
+    }
+
+    /// Returns the type of the snarl with the given boundaries, with
+    /// snarls that don't contain any black bridge edges classified as
+    /// `SnarlType::Trivial`, or None if the snarl doesn't exist.
+    pub fn effective_type(&self, x: Node, y: Node) -> Option<SnarlType> {
+        let snarl_ix = self.get_snarl_ix(x, y)?;
+        Some(self.effective_type_ix(snarl_ix))
+    }
+
+    fn effective_type_ix(&self, snarl_ix: usize) -> SnarlType {
+        if self.is_trivial_ix(snarl_ix) {
+            SnarlType::Trivial
+        } else {
+            self.snarls[&snarl_ix].snarl_type()
+        }
+    }
+
+    /// Classify `x` by the snarls it's a boundary of. A vertex that is
+    /// the boundary of both a chain pair and a bridge pair is a chain
+    /// boundary. Every other vertex is interior, whether or not its
+    /// black edge is contained in a snarl.
+    pub fn node_role(&self, x: Node) -> NodeRole {
+        let mut role = NodeRole::Interior;
+
+        for snarl in self.with_boundary(x) {
+            if snarl.is_chain_pair() {
+                return NodeRole::ChainBoundary;
+            }
+            role = NodeRole::BridgeBoundary;
+        }
+
+        role
+    }
+
+    /// Returns the number of snarls of each type, as classified by
+    /// `effective_type`.
+    pub fn type_counts(&self) -> BTreeMap<SnarlType, usize> {
+        let mut counts = BTreeMap::new();
+
+        for &ix in self.snarls.keys() {
+            *counts.entry(self.effective_type_ix(ix)).or_default() += 1;
+        }
+
+        counts
+    }
+
+    /// Returns the number of black bridge edges marked as contained
+    /// in the snarl with the given boundaries, or None if the snarl
+    /// doesn't exist.
+    pub fn contained_count(&self, x: Node, y: Node) -> Option<usize> {
+        let snarl_ix = self.get_snarl_ix(x, y)?;
+        Some(self.contained_count_ix(snarl_ix))
+    }
+
+    /// Returns the IDs of the GFA segments inside the snarl at rank
+    /// `ix`, sorted. These are the black edges among the
+    /// `interior_edges` of the snarl in `biedged`, the graph the
+    /// snarls were found in, so the segments of the boundaries are not
+    /// included.
+    pub fn original_segments<G: Copy>(
+        &self,
+        ix: usize,
+        biedged: &BiedgedGraph<G>,
+    ) -> Vec<u64> {
+        let snarl = match self.snarls.get(&ix) {
+            Some(snarl) => snarl,
+            None => return Vec::new(),
+        };
+
+        let mut segments = self
+            .interior_edges(snarl.left(), snarl.right(), biedged)
+            .into_iter()
+            .filter(|&(a, b)| {
+                b == a.opposite()
+                    && biedged.edge_weight(a, b).is_some_and(|w| w.black > 0)
+            })
+            .map(|(a, _)| a.to_gfa_id())
+            .collect::<Vec<_>>();
+
+        segments.sort_unstable();
+        segments
+    }
+
+    /// Returns the total sequence length of the black bridge edges
+    /// marked as contained in the snarl with the given boundaries, or
+    /// None if the snarl doesn't exist. `lengths` maps the left-hand
+    /// vertex of each black edge to the length of its segment; edges
+    /// missing from it count as zero.
+    pub fn span_bp(
+        &self,
+        x: Node,
+        y: Node,
+        lengths: &FxHashMap<Node, usize>,
+    ) -> Option<usize> {
+        let snarl_ix = self.get_snarl_ix(x, y)?;
+
+        let span = self
+            .snarl_contains
+            .get(&snarl_ix)
+            .map(|contained| {
+                contained
+                    .iter()
+                    .filter_map(|bridge| lengths.get(&bridge))
+                    .sum()
+            })
+            .unwrap_or(0);
+
+        Some(span)
+    }
+
+    /// Annotate the snarls with their coordinates on a reference
+    /// path, given as a list of GFA segment IDs, each paired with true
+    /// if the path traverses the segment in reverse. `lengths` maps
+    /// segment IDs to their lengths; segments missing from it count
+    /// as zero. Each snarl with both boundaries on the path gets the
+    /// offsets of its boundaries, using the first time the path
+    /// reaches each of them, with the smaller offset first. Replaces
+    /// any previous annotation.
+    pub fn annotate_reference(
+        &mut self,
+        path: &[(u64, bool)],
+        lengths: &FxHashMap<u64, usize>,
+    ) {
+        let mut offsets: FxHashMap<Node, usize> = FxHashMap::default();
+        let mut offset = 0;
+
+        for &(segment, reversed) in path {
+            let (left, right) = Node::from_gfa_id(segment);
+            let (start, end) = if reversed {
+                (right, left)
+            } else {
+                (left, right)
+            };
+
+            offsets.entry(start).or_insert(offset);
+            offset += lengths.get(&segment).copied().unwrap_or(0);
+            offsets.entry(end).or_insert(offset);
+        }
+
+        self.ref_coords.clear();
+
+        for (&ix, snarl) in self.snarls.iter() {
+            let start = offsets.get(&snarl.left());
+            let end = offsets.get(&snarl.right());
+            if let (Some(&start), Some(&end)) = (start, end) {
+                self.ref_coords.insert(ix, (start.min(end), start.max(end)));
+            }
+        }
+    }
+
+    /// Returns the reference coordinates of the snarl with the given
+    /// rank, as set by `annotate_reference`, or None if the snarl
+    /// isn't on the reference path.
+    pub fn reference_coords(&self, ix: usize) -> Option<(usize, usize)> {
+        self.ref_coords.get(&ix).copied()
+    }
+
+    fn contained_count_ix(&self, snarl_ix: usize) -> usize {
+        self.snarl_contains
+            .get(&snarl_ix)
+            .map(|contained| contained.len())
+            .unwrap_or(0)
+    }
+
+    /// Iterate over the snarls in parallel, along with their ranks,
+    /// in no particular order.
+    #[cfg(feature = "parallel")]
+    pub fn par_iter(
+        &self,
+    ) -> impl ParallelIterator<Item = (usize, &Snarl<()>)> + '_ {
+        self.snarls.par_iter().map(|(&ix, snarl)| (ix, snarl))
+    }
+
+    /// Iterates the snarls that contain at least one black bridge
+    /// edge.
+    pub fn non_trivial_snarls(&self) -> impl Iterator<Item = Snarl<()>> + '_ {
+        self.snarls.iter().filter_map(move |(&ix, &snarl)| {
+            if self.is_trivial_ix(ix) {
+                None
+            } else {
+                Some(snarl)
+            }
+        })
+    }
+
+    /// Check that the boundaries of every snarl in the map are
+    /// vertices of `biedged`, and that every black edge marked in the
+    /// snarls' contained edges exists in it. Returns the
+    /// inconsistencies found, ordered by snarl rank, or an empty
+    /// vector if there are none.
+    pub fn audit<G: Copy>(
+        &self,
+        biedged: &BiedgedGraph<G>,
+    ) -> Vec<SnarlAuditError> {
+        let graph = &biedged.graph;
+
+        let mut errors = Vec::new();
+
+        for (&ix, snarl) in self.snarls.iter() {
+            for &node in [snarl.left(), snarl.right()].iter() {
+                if !graph.contains_node(node) {
+                    errors.push(SnarlAuditError::DanglingBoundary { ix, node });
+                }
+            }
+
+            if let Some(contained) = self.snarl_contains.get(&ix) {
+                for edge in contained.iter() {
+                    let exists = graph
+                        .edge_weight(edge, edge.opposite())
+                        .map(|w| w.black > 0)
+                        .unwrap_or(false);
+
+                    if !exists {
+                        errors.push(SnarlAuditError::MissingContainedEdge {
+                            ix,
+                            edge,
+                        });
+                    }
+                }
+            }
+        }
+
+        errors.sort_unstable();
+        errors
+    }
+
     /// Returns a map from black bridge edges to snarls containing the edge
     pub fn invert_contains(&self) -> FxHashMap<Node, FxHashSet<Snarl<()>>> {
         let mut res: FxHashMap<Node, FxHashSet<Snarl<()>>> = Default::default();
@@ -508,13 +1598,2018 @@ impl SnarlMap {
         for (&snarl_ix, contained) in self.snarl_contains.iter() {
             let snarl = *self.snarls.get(&snarl_ix).unwrap();
 
-            for (&bridge, &contains) in contained.iter() {
-                if contains {
-                    res.entry(bridge).or_default().insert(snarl);
-                }
+            for bridge in contained.iter() {
+                res.entry(bridge).or_default().insert(snarl);
             }
         }
 
         res
     }
+
+    /// Classifies the snarl with the boundaries `x` and `y` by the
+    /// number of traversals between them, as found by
+    /// `snarl_traversals`. Returns None if the snarl doesn't exist.
+    pub fn complexity<G: Copy>(
+        &self,
+        x: Node,
+        y: Node,
+        biedged: &BiedgedGraph<G>,
+    ) -> Option<SnarlComplexity> {
+        self.get_snarl_ix(x, y)?;
+        // no need to enumerate more than one path past a bubble
+        let paths = snarl_traversals(self, biedged, x, y, 3);
+        if paths.len() <= 2 {
+            Some(SnarlComplexity::Simple)
+        } else {
+            Some(SnarlComplexity::Complex)
+        }
+    }
+
+    /// Counts the traversals of the snarl with the boundaries `x` and
+    /// `y`, as found by `snarl_traversals`, which approximates the
+    /// number of alleles of a bubble. Stops counting at `cap`. Returns
+    /// None if the snarl doesn't exist.
+    pub fn allele_count<G: Copy>(
+        &self,
+        x: Node,
+        y: Node,
+        biedged: &BiedgedGraph<G>,
+        cap: usize,
+    ) -> Option<usize> {
+        self.get_snarl_ix(x, y)?;
+        Some(snarl_traversals(self, biedged, x, y, cap).len())
+    }
+
+    /// Returns true if the interior of the snarl with the boundaries
+    /// `x` and `y`, as given by `interior_edges`, has no directed
+    /// cycles, i.e. no walk inside the snarl can return to a segment
+    /// in the same orientation. Returns None if the snarl doesn't
+    /// exist.
+    pub fn is_acyclic<G: Copy>(
+        &self,
+        x: Node,
+        y: Node,
+        biedged: &BiedgedGraph<G>,
+    ) -> Option<bool> {
+        self.get_snarl_ix(x, y)?;
+
+        let mut gray: FxHashMap<Node, Vec<Node>> = FxHashMap::default();
+        let mut black: FxHashSet<Node> = FxHashSet::default();
+
+        for (a, b) in self.interior_edges(x, y, biedged) {
+            let w = match biedged.graph.edge_weight(a, b) {
+                Some(w) => *w,
+                None => continue,
+            };
+
+            if w.gray > 0 {
+                gray.entry(a).or_default().push(b);
+                if a != b {
+                    gray.entry(b).or_default().push(a);
+                }
+            }
+
+            if w.black > 0 && b == a.opposite() {
+                black.insert(a.left());
+            }
+        }
+
+        // A walk that enters a segment through the vertex `v` leaves
+        // it through the opposite vertex, continuing along one of its
+        // gray edges. Walks stop when they reach a boundary.
+        let successors = |v: Node| -> &[Node] {
+            let exit = v.opposite();
+            let at_boundary = [x, y].iter().any(|&b| b == v || b == exit);
+            if at_boundary || !black.contains(&v.left()) {
+                return &[];
+            }
+            gray.get(&v.opposite()).map(|n| n.as_slice()).unwrap_or(&[])
+        };
+
+        let mut roots = gray.keys().copied().collect::<Vec<_>>();
+        roots.sort_unstable();
+
+        // false while a vertex is on the DFS stack, true once finished
+        let mut finished: FxHashMap<Node, bool> = FxHashMap::default();
+
+        for root in roots {
+            if finished.contains_key(&root) {
+                continue;
+            }
+
+            finished.insert(root, false);
+            let mut stack: Vec<(Node, usize)> = vec![(root, 0)];
+
+            while let Some((current, next_ix)) = stack.last_mut() {
+                match successors(*current).get(*next_ix) {
+                    Some(&next) => {
+                        *next_ix += 1;
+                        match finished.get(&next) {
+                            Some(false) => return Some(false),
+                            Some(true) => (),
+                            None => {
+                                finished.insert(next, false);
+                                stack.push((next, 0));
+                            }
+                        }
+                    }
+                    None => {
+                        finished.insert(*current, true);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        Some(true)
+    }
+
+    /// Returns the edges of `biedged` that lie inside the snarl with
+    /// the boundaries `x` and `y`, each as a sorted pair of vertices.
+    /// The inside is everything that can be reached from the
+    /// boundaries without crossing the black edge of either boundary,
+    /// so the boundaries are left through their gray edges. Returns an
+    /// empty list if the snarl doesn't exist, or if that walk reaches
+    /// the other side of a boundary, as the boundaries then don't
+    /// enclose anything.
+    pub fn interior_edges<G: Copy>(
+        &self,
+        x: Node,
+        y: Node,
+        biedged: &BiedgedGraph<G>,
+    ) -> Vec<(Node, Node)> {
+        if self.get_snarl_ix(x, y).is_none() {
+            return Vec::new();
+        }
+
+        let mut edges = snarl_interior(biedged, x, y)
+            .into_iter()
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+    }
+
+    /// Returns the net graph of the snarl with the boundaries `x` and
+    /// `y`: the subgraph of `biedged` made up of the edges returned by
+    /// `interior_edges`, where the interior of each child snarl is
+    /// replaced by a black edge between the child's boundaries. A
+    /// child is any other snarl in the map with both boundaries in the
+    /// subgraph, and an interior that lies within it without passing
+    /// through `x` or `y`. Larger children are contracted first, so
+    /// the children nested inside them are contracted along with
+    /// them. Returns an empty graph if the snarl doesn't exist.
+    pub fn snarl_net_graph<G: Copy>(
+        &self,
+        x: Node,
+        y: Node,
+        biedged: &BiedgedGraph<G>,
+    ) -> BiedgedGraph<G> {
+        let mut net_graph: BiedgedGraph<G> = BiedgedGraph {
+            max_net_vertex: biedged.max_net_vertex,
+            max_chain_vertex: biedged.max_chain_vertex,
+            ..Default::default()
+        };
+
+        let snarl = match self.get(x, y) {
+            Some(snarl) => snarl,
+            None => return net_graph,
+        };
+        let (x, y) = (snarl.left(), snarl.right());
+
+        let mut edges = self
+            .interior_edges(x, y, biedged)
+            .into_iter()
+            .collect::<FxHashSet<_>>();
+
+        let nodes = edges
+            .iter()
+            .flat_map(|&(a, b)| std::iter::once(a).chain(std::iter::once(b)))
+            .collect::<FxHashSet<_>>();
+
+        let mut children = self
+            .snarls
+            .values()
+            .filter(|child| {
+                (child.left(), child.right()) != (x, y)
+                    && nodes.contains(&child.left())
+                    && nodes.contains(&child.right())
+            })
+            .map(|&child| {
+                let child_edges =
+                    self.interior_edges(child.left(), child.right(), biedged);
+                (child, child_edges)
+            })
+            .collect::<Vec<_>>();
+
+        children.sort_by(|(a, a_edges), (b, b_edges)| {
+            b_edges.len().cmp(&a_edges.len()).then(a.cmp(b))
+        });
+
+        let mut contracted: Vec<(Node, Node)> = Vec::new();
+
+        for (child, child_edges) in children {
+            let (left, right) = (child.left(), child.right());
+
+            let escapes = child_edges.iter().any(|&(a, b)| {
+                let interior = |n: Node| n != left && n != right;
+                (interior(a) && (a == x || a == y))
+                    || (interior(b) && (b == x || b == y))
+            });
+
+            if escapes || !child_edges.iter().all(|e| edges.contains(e)) {
+                continue;
+            }
+
+            for edge in child_edges.iter() {
+                edges.remove(edge);
+            }
+            contracted.push((left, right));
+        }
+
+        net_graph.graph.add_node(x);
+        net_graph.graph.add_node(y);
+
+        for (a, b) in edges {
+            if let Some(&w) = biedged.graph.edge_weight(a, b) {
+                net_graph.add_edge(a, b, w);
+            }
+        }
+
+        for (left, right) in contracted {
+            net_graph.add_edge(left, right, BiedgedWeight::black(1));
+        }
+
+        net_graph
+    }
+}
+
+/// Collect the edges of `biedged` that can be reached from `x` and
+/// `y` without crossing the black edge of either, each as a sorted
+/// pair of vertices. If `x` and `y` are the boundaries of a snarl,
+/// these are the edges inside it.
+///
+/// If the walk reaches the other side of either boundary, the two
+/// boundaries don't separate anything from the rest of the graph, and
+/// the set is empty.
+pub(crate) fn snarl_interior<G: Copy>(
+    biedged: &BiedgedGraph<G>,
+    x: Node,
+    y: Node,
+) -> FxHashSet<(Node, Node)> {
+    let graph = &biedged.graph;
+
+    let is_boundary_black_edge = |a: Node, b: Node, w: &BiedgedWeight| {
+        (a == x || a == y) && b == a.opposite() && w.black_only()
+    };
+
+    let is_outside = |n: Node| {
+        (n == x.opposite() && n != y) || (n == y.opposite() && n != x)
+    };
+
+    let mut edges: FxHashSet<(Node, Node)> = FxHashSet::default();
+    let mut visited: FxHashSet<Node> = FxHashSet::default();
+
+    let mut stack = [x, y]
+        .iter()
+        .copied()
+        .filter(|&n| graph.contains_node(n))
+        .collect::<Vec<_>>();
+
+    while let Some(current) = stack.pop() {
+        if !visited.insert(current) {
+            continue;
+        }
+
+        for (_, other, w) in graph.edges(current) {
+            if is_boundary_black_edge(current, other, w)
+                || is_boundary_black_edge(other, current, w)
+            {
+                continue;
+            }
+
+            if is_outside(other) {
+                return FxHashSet::default();
+            }
+
+            edges.insert((current.min(other), current.max(other)));
+
+            if !visited.contains(&other) {
+                stack.push(other);
+            }
+        }
+    }
+
+    edges
+}
+
+/// Check whether `x` and `y` could be the boundaries of a snarl in
+/// `biedged`, before inserting it into a `SnarlMap`: they must be
+/// distinct vertices, both must be the endpoint of a black edge, and
+/// there must be a path of at least one edge between them.
+pub fn is_valid_boundary<G: Copy>(
+    biedged: &BiedgedGraph<G>,
+    x: Node,
+    y: Node,
+) -> bool {
+    if x == y || !biedged.contains_node(x) || !biedged.contains_node(y) {
+        return false;
+    }
+
+    if biedged.black_degree(x) == 0 || biedged.black_degree(y) == 0 {
+        return false;
+    }
+
+    petgraph::algo::has_path_connecting(&biedged.graph, x, y, None)
+}
+
+/// Enumerate the simple paths through the snarl with boundaries `x`
+/// and `y`, from `x` to `y`, using only the edges returned by
+/// `SnarlMap::interior_edges`. Each path alternates between gray and
+/// black edges, starting and ending with a gray edge, and is given as
+/// the list of vertices along it. At most `max_paths` paths are
+/// returned, in sorted order.
+pub fn snarl_traversals<G: Copy>(
+    map: &SnarlMap,
+    biedged: &BiedgedGraph<G>,
+    x: Node,
+    y: Node,
+    max_paths: usize,
+) -> Vec<Vec<Node>> {
+    let mut adjacent: FxHashMap<Node, Vec<(Node, BiedgedWeight)>> =
+        FxHashMap::default();
+
+    for (a, b) in map.interior_edges(x, y, biedged) {
+        if let Some(&w) = biedged.graph.edge_weight(a, b) {
+            adjacent.entry(a).or_default().push((b, w));
+            if a != b {
+                adjacent.entry(b).or_default().push((a, w));
+            }
+        }
+    }
+
+    for neighbors in adjacent.values_mut() {
+        neighbors.sort_unstable_by_key(|&(n, _)| n);
+    }
+
+    // Each stack entry holds the index of the next neighbor to try
+    // from the corresponding vertex on the path
+    let mut path: Vec<Node> = vec![x];
+    let mut stack: Vec<usize> = vec![0];
+    let mut on_path: FxHashSet<Node> = std::iter::once(x).collect();
+
+    let mut paths = Vec::new();
+
+    while let Some(next_ix) = stack.last_mut() {
+        if paths.len() >= max_paths {
+            break;
+        }
+
+        let current = *path.last().unwrap();
+        // edges at even steps along the path are gray
+        let gray_step = path.len() & 1 == 1;
+
+        let neighbors = adjacent.get(&current).map(|n| n.as_slice());
+
+        match neighbors.and_then(|n| n.get(*next_ix)) {
+            Some(&(other, w)) => {
+                *next_ix += 1;
+
+                let has_edge = if gray_step { w.gray > 0 } else { w.black > 0 };
+
+                if !has_edge || on_path.contains(&other) {
+                    continue;
+                }
+
+                if other == y {
+                    if gray_step {
+                        let mut found = path.clone();
+                        found.push(y);
+                        paths.push(found);
+                    }
+                    continue;
+                }
+
+                on_path.insert(other);
+                path.push(other);
+                stack.push(0);
+            }
+            None => {
+                stack.pop();
+                if let Some(n) = path.pop() {
+                    on_path.remove(&n);
+                }
+            }
+        }
+    }
+
+    paths.sort();
+    paths
+}
+
+/// The differences between two snarl maps, as found by
+/// `diff_snarl_maps`. Snarls are matched by their `boundary_key`, and
+/// each list is sorted by it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SnarlMapDiff {
+    /// Snarls that are only in the first map
+    pub only_in_a: Vec<Snarl<()>>,
+    /// Snarls that are only in the second map
+    pub only_in_b: Vec<Snarl<()>>,
+    /// Snarls in both maps, with different contained black edges
+    pub containment_differs: Vec<Snarl<()>>,
+}
+
+impl SnarlMapDiff {
+    /// Returns true if the maps had the same snarls, with the same
+    /// contained edges.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty()
+            && self.only_in_b.is_empty()
+            && self.containment_differs.is_empty()
+    }
+}
+
+/// Compare two snarl maps, ignoring the ranks of their snarls.
+pub fn diff_snarl_maps(a: &SnarlMap, b: &SnarlMap) -> SnarlMapDiff {
+    type Keyed = BTreeMap<(Node, Node, SnarlType), (Snarl<()>, usize)>;
+
+    let by_key = |map: &SnarlMap| -> Keyed {
+        map.snarls
+            .iter()
+            .map(|(&ix, &snarl)| (snarl.boundary_key(), (snarl, ix)))
+            .collect()
+    };
+
+    let a_snarls = by_key(a);
+    let b_snarls = by_key(b);
+
+    let empty = ContainedEdges::default();
+
+    let mut diff = SnarlMapDiff::default();
+
+    for (key, &(snarl, a_ix)) in a_snarls.iter() {
+        match b_snarls.get(key) {
+            None => diff.only_in_a.push(snarl),
+            Some(&(_, b_ix)) => {
+                let a_contains = a.snarl_contains.get(&a_ix).unwrap_or(&empty);
+                let b_contains = b.snarl_contains.get(&b_ix).unwrap_or(&empty);
+                if a_contains != b_contains {
+                    diff.containment_differs.push(snarl);
+                }
+            }
+        }
+    }
+
+    diff.only_in_b = b_snarls
+        .iter()
+        .filter(|(key, _)| !a_snarls.contains_key(key))
+        .map(|(_, &(snarl, _))| snarl)
+        .collect();
+
+    diff
+}
+
+/// Write the regions of the snarls on a reference path as
+/// tab-separated CHROM, POS, END, and ID columns, after a header
+/// line. The regions are the coordinates set by
+/// `SnarlMap::annotate_reference`, written 1-based and inclusive, as
+/// in a VCF, so only the snarls with both boundaries on the path are
+/// included. CHROM is `chrom`, the name of the reference path, and
+/// the ID is the names of the boundaries, as in `write_snarls_bed`,
+/// joined with `-`. The rows are ordered by their positions.
+pub fn write_snarl_regions<W: Write>(
+    map: &SnarlMap,
+    name_map: &NameMap,
+    chrom: &str,
+    w: &mut W,
+) -> io::Result<()> {
+    let name = |n: Node| {
+        projected_node_name(name_map, n).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("no segment name for node {}", n.id),
+            )
+        })
+    };
+
+    writeln!(w, "#CHROM\tPOS\tEND\tID")?;
+
+    let mut regions = map
+        .ref_coords
+        .iter()
+        .filter_map(|(ix, &(start, end))| {
+            let snarl = map.snarls.get(ix)?;
+            Some((start, end, *snarl))
+        })
+        .collect::<Vec<_>>();
+    regions.sort_unstable();
+
+    for (start, end, snarl) in regions {
+        let left = name(snarl.left())?;
+        let right = name(snarl.right())?;
+
+        writeln!(w, "{}\t{}\t{}\t{}-{}", chrom, start + 1, end, left, right)?;
+    }
+
+    Ok(())
+}
+
+/// Write the snarls in the map as tab-separated lines, ordered by
+/// their boundaries. Each line contains the names of the left and right
+/// boundaries, as produced by `projected_node_name`, the snarl type,
+/// and the number of black bridge edges contained in the snarl.
+pub fn write_snarls_bed<W: Write>(
+    snarl_map: &SnarlMap,
+    name_map: &NameMap,
+    w: &mut W,
+) -> io::Result<()> {
+    let mut ranks = snarl_map.snarls.keys().copied().collect::<Vec<_>>();
+    ranks.sort_unstable_by_key(|ix| snarl_map.snarls[ix]);
+
+    for ix in ranks {
+        let snarl = snarl_map.snarls[&ix];
+
+        let name = |n: Node| {
+            projected_node_name(name_map, n).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("no segment name for node {}", n.id),
+                )
+            })
+        };
+
+        let left = name(snarl.left())?;
+        let right = name(snarl.right())?;
+
+        let ty = match snarl.snarl_type() {
+            SnarlType::ChainPair => "chain",
+            SnarlType::BridgePair => "bridge",
+            SnarlType::Trivial => "trivial",
+        };
+
+        let contained = snarl_map.contained_count_ix(ix);
+
+        writeln!(w, "{}\t{}\t{}\t{}", left, right, ty, contained)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_and_snarl_formatting() {
+        let (left, right) = Node::from_gfa_id(7);
+
+        assert_eq!(format!("{:?}", left), "Node(7L)");
+        assert_eq!(format!("{:?}", right), "Node(7R)");
+        assert_eq!(right.to_string(), "Node(7R)");
+
+        let snarl = Snarl::<()>::chain_pair(Node::new(15), Node::new(18));
+        assert_eq!(snarl.to_string(), "ChainPair(Node(7R), Node(9L))");
+        assert_eq!(
+            format!("{:?}", snarl),
+            "Snarl { left: Node(7R), right: Node(9L), ty: ChainPair, data: () }"
+        );
+
+        let snarl = Snarl::<()>::bridge_pair(Node::new(4), Node::new(1));
+        assert_eq!(snarl.to_string(), "BridgePair(Node(0R), Node(2L))");
+    }
+
+    #[test]
+    fn node_between() {
+        let a = Node::new(4);
+        let b = Node::new(9);
+
+        assert!(Node::new(4).between(a, b));
+        assert!(Node::new(9).between(a, b));
+        assert!(Node::new(6).between(a, b));
+        assert!(Node::new(6).between(b, a));
+
+        assert!(!Node::new(3).between(a, b));
+        assert!(!Node::new(10).between(b, a));
+
+        assert!(Node::new(5).between(Node::new(5), Node::new(5)));
+    }
+
+    #[test]
+    fn node_black_edge_range() {
+        let (left, right) = Node::from_gfa_id(7);
+
+        assert_eq!(left.black_edge_range(), (14, 15));
+        assert_eq!(right.black_edge_range(), (14, 15));
+
+        let (lo, hi) = right.black_edge_range();
+        assert!(left.between(lo.into(), hi.into()));
+        assert!(right.between(lo.into(), hi.into()));
+        assert!(!Node::new(16).between(lo.into(), hi.into()));
+    }
+
+    #[test]
+    fn compact_removed_snarls() {
+        let mut snarl_map = SnarlMap::default();
+
+        let boundaries = [(2, 5), (6, 11), (12, 17), (18, 21), (22, 29)];
+
+        for &(x, y) in boundaries.iter() {
+            snarl_map.insert(Snarl::chain_pair(x.into(), y.into()));
+            snarl_map.mark_snarl(x.into(), y.into(), Node::new(x + 1), true);
+        }
+
+        assert!(snarl_map.remove(Node::new(6), Node::new(11)).is_some());
+        assert!(snarl_map.remove(Node::new(18), Node::new(21)).is_some());
+        assert!(snarl_map.remove(Node::new(18), Node::new(21)).is_none());
+
+        let mut ranks = snarl_map.snarls.keys().copied().collect::<Vec<_>>();
+        ranks.sort_unstable();
+        assert_eq!(ranks, vec![0, 2, 4]);
+
+        let rank_map = snarl_map.compact();
+
+        assert_eq!(rank_map.len(), 3);
+        assert_eq!(rank_map[&0], 0);
+        assert_eq!(rank_map[&2], 1);
+        assert_eq!(rank_map[&4], 2);
+
+        let mut ranks = snarl_map.snarls.keys().copied().collect::<Vec<_>>();
+        ranks.sort_unstable();
+        assert_eq!(ranks, vec![0, 1, 2]);
+
+        for &(x, y) in [(2, 5), (12, 17), (22, 29)].iter() {
+            let contains =
+                snarl_map.snarl_contains(x.into(), y.into()).unwrap();
+            assert_eq!(contains.len(), 1);
+            assert!(contains.contains(Node::new(x)));
+        }
+
+        assert!(snarl_map.get(Node::new(6), Node::new(11)).is_none());
+
+        snarl_map.insert(Snarl::chain_pair(Node::new(30), Node::new(33)));
+        assert_eq!(
+            snarl_map.get_snarl_ix(Node::new(30), Node::new(33)),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn trivial_snarls() {
+        let mut snarl_map = SnarlMap::default();
+
+        let trivial = Snarl::chain_pair(Node::new(2), Node::new(5));
+        let unmarked = Snarl::chain_pair(Node::new(6), Node::new(9));
+        let non_trivial = Snarl::bridge_pair(Node::new(10), Node::new(17));
+
+        snarl_map.insert(trivial);
+        snarl_map.insert(unmarked);
+        snarl_map.insert(non_trivial);
+
+        snarl_map.mark_snarl(Node::new(2), Node::new(5), Node::new(3), false);
+        snarl_map.mark_snarl(
+            Node::new(10),
+            Node::new(17),
+            Node::new(11),
+            false,
+        );
+        snarl_map.mark_snarl(Node::new(10), Node::new(17), Node::new(13), true);
+
+        assert_eq!(
+            snarl_map.is_trivial(Node::new(2), Node::new(5)),
+            Some(true)
+        );
+        assert_eq!(
+            snarl_map.is_trivial(Node::new(9), Node::new(6)),
+            Some(true)
+        );
+        assert_eq!(
+            snarl_map.is_trivial(Node::new(10), Node::new(17)),
+            Some(false)
+        );
+        assert_eq!(snarl_map.is_trivial(Node::new(2), Node::new(9)), None);
+
+        let non_trivials = snarl_map.non_trivial_snarls().collect::<Vec<_>>();
+        assert_eq!(non_trivials, vec![non_trivial]);
+    }
+
+    #[test]
+    fn merge_snarl_maps() {
+        let mut first = SnarlMap::default();
+        first.insert(Snarl::chain_pair(Node::new(2), Node::new(5)));
+        first.insert(Snarl::chain_pair(Node::new(6), Node::new(9)));
+        first.mark_snarl(Node::new(6), Node::new(9), Node::new(20), false);
+
+        let mut second = SnarlMap::default();
+        second.insert(Snarl::bridge_pair(Node::new(11), Node::new(16)));
+        second.insert(Snarl::chain_pair(Node::new(9), Node::new(6)));
+        second.insert(Snarl::chain_pair(Node::new(21), Node::new(24)));
+        second.mark_snarl(Node::new(6), Node::new(9), Node::new(20), true);
+        second.mark_snarl(Node::new(6), Node::new(9), Node::new(22), false);
+        second.mark_snarl(Node::new(11), Node::new(16), Node::new(12), true);
+
+        first.merge(second);
+
+        assert_eq!(first.snarls.len(), 4);
+
+        let mut ranks = first.snarls.keys().copied().collect::<Vec<_>>();
+        ranks.sort_unstable();
+        assert_eq!(ranks, vec![0, 1, 2, 3]);
+
+        assert_eq!(first.get_snarl_ix(Node::new(6), Node::new(9)), Some(1));
+        assert_eq!(first.get_snarl_ix(Node::new(11), Node::new(16)), Some(2));
+        assert_eq!(first.get_snarl_ix(Node::new(21), Node::new(24)), Some(3));
+
+        let contains =
+            first.snarl_contains(Node::new(6), Node::new(9)).unwrap();
+        assert_eq!(contains.len(), 1);
+        assert!(contains.contains(Node::new(20)));
+        assert!(!contains.contains(Node::new(22)));
+
+        assert_eq!(first.is_trivial(Node::new(11), Node::new(16)), Some(false));
+        assert_eq!(first.with_boundary(Node::new(9)).count(), 1);
+    }
+
+    #[test]
+    fn contained_counts() {
+        let mut snarl_map = SnarlMap::default();
+
+        let boundaries = [(2, 9), (10, 13), (14, 21)];
+
+        for &(x, y) in boundaries.iter() {
+            snarl_map.insert(Snarl::chain_pair(x.into(), y.into()));
+        }
+
+        let mut expected: FxHashMap<(u64, u64), FxHashMap<Node, bool>> =
+            FxHashMap::default();
+
+        let marks = [
+            ((2, 9), 4, true),
+            ((2, 9), 6, true),
+            ((2, 9), 12, false),
+            ((10, 13), 12, false),
+            ((14, 21), 16, true),
+            ((14, 21), 19, true),
+            ((14, 21), 17, false),
+        ];
+
+        for &((x, y), bridge, contains) in marks.iter() {
+            let bridge = Node::new(bridge);
+            snarl_map.mark_snarl(x.into(), y.into(), bridge, contains);
+            expected
+                .entry((x, y))
+                .or_default()
+                .insert(bridge.left(), contains);
+        }
+
+        for &(x, y) in boundaries.iter() {
+            let manual = expected[&(x, y)].values().filter(|&&c| c).count();
+            assert_eq!(
+                snarl_map.contained_count(x.into(), y.into()),
+                Some(manual)
+            );
+        }
+
+        assert_eq!(
+            snarl_map.contained_count(Node::new(2), Node::new(9)),
+            Some(2)
+        );
+        assert_eq!(
+            snarl_map.contained_count(Node::new(10), Node::new(13)),
+            Some(0)
+        );
+        // 16 and 17 are the same black edge, which was last marked as
+        // not contained
+        assert_eq!(
+            snarl_map.contained_count(Node::new(14), Node::new(21)),
+            Some(1)
+        );
+        assert_eq!(
+            snarl_map.contained_count(Node::new(2), Node::new(13)),
+            None
+        );
+    }
+
+    #[test]
+    fn snarl_span_bp() {
+        let mut snarl_map = SnarlMap::default();
+
+        snarl_map.insert(Snarl::bridge_pair(Node::new(2), Node::new(13)));
+        snarl_map.insert(Snarl::chain_pair(Node::new(14), Node::new(17)));
+
+        snarl_map.mark_snarl(Node::new(2), Node::new(13), Node::new(4), true);
+        snarl_map.mark_snarl(Node::new(2), Node::new(13), Node::new(7), true);
+        snarl_map.mark_snarl(Node::new(2), Node::new(13), Node::new(8), false);
+        snarl_map.mark_snarl(Node::new(2), Node::new(13), Node::new(10), true);
+
+        let lengths: FxHashMap<Node, usize> = (0..10)
+            .map(|i| (Node::from_gfa_id(i).0, 10 * i as usize + 1))
+            .collect();
+
+        // Segments 2, 3, and 5 are contained, segment 4 isn't
+        assert_eq!(
+            snarl_map.span_bp(Node::new(2), Node::new(13), &lengths),
+            Some(21 + 31 + 51)
+        );
+        assert_eq!(
+            snarl_map.span_bp(Node::new(14), Node::new(17), &lengths),
+            Some(0)
+        );
+        assert_eq!(
+            snarl_map.span_bp(Node::new(2), Node::new(17), &lengths),
+            None
+        );
+    }
+
+    #[test]
+    fn left_and_right_boundaries() {
+        let mut snarl_map = SnarlMap::default();
+
+        let before = Snarl::chain_pair(Node::new(3), Node::new(8));
+        let after = Snarl::bridge_pair(Node::new(8), Node::new(15));
+
+        snarl_map.insert(before);
+        snarl_map.insert(after);
+
+        let x = Node::new(8);
+
+        assert_eq!(snarl_map.with_boundary(x).count(), 2);
+
+        assert_eq!(
+            snarl_map.snarls_with_left(x).collect::<Vec<_>>(),
+            vec![after]
+        );
+        assert_eq!(
+            snarl_map.snarls_with_right(x).collect::<Vec<_>>(),
+            vec![before]
+        );
+
+        assert_eq!(snarl_map.snarls_with_left(Node::new(3)).count(), 1);
+        assert_eq!(snarl_map.snarls_with_right(Node::new(3)).count(), 0);
+        assert_eq!(snarl_map.snarls_with_left(Node::new(4)).count(), 0);
+    }
+
+    #[test]
+    fn self_boundary_snarl_iterated_once() {
+        let mut snarl_map = SnarlMap::default();
+
+        let x = Node::new(8);
+
+        let looped = Snarl::chain_pair(x, x);
+        let other = Snarl::chain_pair(Node::new(3), x);
+
+        snarl_map.insert(looped);
+        snarl_map.insert(other);
+
+        let mut iter = snarl_map.with_boundary(x);
+        let mut snarls = iter.by_ref().collect::<Vec<_>>();
+        snarls.sort();
+
+        assert_eq!(snarls, vec![other, looped]);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn snarls_by_rank() {
+        let mut snarl_map = SnarlMap::default();
+
+        let snarl = Snarl::chain_pair(Node::new(9), Node::new(4));
+        snarl_map.insert(snarl);
+
+        let ix = snarl_map.get_snarl_ix(Node::new(4), Node::new(9)).unwrap();
+
+        assert_eq!(snarl_map.snarl_by_rank(ix), Some(snarl));
+        assert_eq!(
+            snarl_map.boundaries(ix),
+            Some((Node::new(4), Node::new(9)))
+        );
+
+        let (x, y) = snarl_map.boundaries(ix).unwrap();
+        assert_eq!(snarl_map.get_snarl_ix(x, y), Some(ix));
+
+        assert_eq!(snarl_map.snarl_by_rank(ix + 1), None);
+        assert_eq!(snarl_map.boundaries(ix + 1), None);
+    }
+
+    #[test]
+    fn contained_edges_match_marks() {
+        let mut snarl_map = SnarlMap::default();
+
+        let (x, y) = (Node::new(4), Node::new(13));
+        snarl_map.insert(Snarl::chain_pair(x, y));
+
+        // the last mark of each edge decides whether it's contained,
+        // as when the marks were stored as a map of bools
+        let marks = [(6, true), (9, true), (10, false), (11, true), (6, false)];
+
+        let mut expected: FxHashMap<Node, bool> = FxHashMap::default();
+
+        for &(bridge, contains) in marks.iter() {
+            let bridge = Node::new(bridge);
+            snarl_map.mark_snarl(x, y, bridge, contains);
+            expected.insert(bridge.left(), contains);
+        }
+
+        let contained = snarl_map.snarl_contains(x, y).unwrap();
+
+        let mut from_bools = expected
+            .iter()
+            .filter(|(_, &c)| c)
+            .map(|(&n, _)| n)
+            .collect::<Vec<_>>();
+        from_bools.sort();
+
+        let mut from_set = contained.iter().collect::<Vec<_>>();
+        from_set.sort();
+
+        assert_eq!(from_set, from_bools);
+        assert_eq!(from_set, vec![Node::new(8), Node::new(10)]);
+
+        // either vertex of a black edge identifies it
+        assert!(contained.contains(Node::new(9)));
+        assert!(contained.contains(Node::new(8)));
+        assert!(contained.contains(Node::new(11)));
+        assert!(!contained.contains(Node::new(6)));
+        assert!(!contained.contains(Node::new(7)));
+
+        assert_eq!(snarl_map.contained_count(x, y), Some(2));
+
+        let inverted = snarl_map.invert_contains();
+        let mut bridges = inverted.keys().copied().collect::<Vec<_>>();
+        bridges.sort();
+        assert_eq!(bridges, vec![Node::new(8), Node::new(10)]);
+    }
+
+    #[test]
+    fn snarl_boundary_keys() {
+        let mut first = SnarlMap::default();
+        let mut second = SnarlMap::default();
+
+        first.insert(Snarl::chain_pair(Node::new(3), Node::new(8)));
+        first.insert(Snarl::bridge_pair(Node::new(11), Node::new(16)));
+        second.insert(Snarl::bridge_pair(Node::new(16), Node::new(11)));
+
+        first.mark_snarl(Node::new(11), Node::new(16), Node::new(12), true);
+        second.mark_snarl(Node::new(11), Node::new(16), Node::new(12), true);
+
+        let a = first.get(Node::new(11), Node::new(16)).unwrap();
+        let b = second.get(Node::new(11), Node::new(16)).unwrap();
+
+        assert_ne!(
+            first.get_snarl_ix(Node::new(11), Node::new(16)),
+            second.get_snarl_ix(Node::new(11), Node::new(16))
+        );
+
+        assert_eq!(a.boundary_key(), b.boundary_key());
+        assert_eq!(
+            a.boundary_key(),
+            (Node::new(11), Node::new(16), SnarlType::BridgePair)
+        );
+
+        let with_data =
+            Snarl::bridge_pair_with(Node::new(11), Node::new(16), 7u8);
+        assert_eq!(with_data.boundary_key(), a.boundary_key());
+
+        let chain = Snarl::<()>::chain_pair(Node::new(11), Node::new(16));
+        assert_ne!(chain.boundary_key(), a.boundary_key());
+
+        assert_eq!(
+            first.invert_contains()[&Node::new(12)],
+            second.invert_contains()[&Node::new(12)]
+        );
+    }
+
+    #[test]
+    fn try_insert_duplicate() {
+        let mut snarl_map = SnarlMap::default();
+
+        let snarl = Snarl::chain_pair(Node::new(3), Node::new(8));
+
+        assert_eq!(snarl_map.try_insert(snarl), Ok(0));
+        assert_eq!(
+            snarl_map.try_insert(Snarl::chain_pair(Node::new(8), Node::new(3))),
+            Err(SnarlConflict::Duplicate(0))
+        );
+        assert_eq!(snarl_map.snarls.len(), 1);
+    }
+
+    #[test]
+    fn try_insert_conflicting_boundary() {
+        let mut snarl_map = SnarlMap::default();
+
+        let first = Snarl::chain_pair(Node::new(3), Node::new(8));
+        let second = Snarl::bridge_pair(Node::new(10), Node::new(15));
+        let conflict = Snarl::bridge_pair(Node::new(8), Node::new(21));
+
+        assert_eq!(snarl_map.try_insert(first), Ok(0));
+        assert_eq!(snarl_map.try_insert(second), Ok(1));
+
+        assert_eq!(
+            snarl_map.try_insert(conflict),
+            Err(SnarlConflict::Boundary {
+                node: Node::new(8),
+                ix: 0
+            })
+        );
+
+        assert_eq!(snarl_map.snarls.len(), 2);
+        assert!(snarl_map.get(Node::new(8), Node::new(21)).is_none());
+
+        // the non-strict insert still accepts the snarl
+        snarl_map.insert(conflict);
+        assert_eq!(
+            snarl_map.get_snarl_ix(Node::new(21), Node::new(8)),
+            Some(2)
+        );
+    }
+
+    fn paper_graph() -> (BiedgedGraph<Biedged>, NameMap) {
+        use gfa::{gfa::GFA, parser::GFAParser};
+
+        let parser = GFAParser::new();
+        let vec_gfa: GFA<Vec<u8>, ()> =
+            parser.parse_file("./test/gfas/paper.gfa").unwrap();
+
+        let name_map = NameMap::build_from_gfa(&vec_gfa);
+        let gfa = name_map.gfa_bytestring_to_usize(&vec_gfa, false).unwrap();
+
+        (BiedgedGraph::from_gfa(&gfa), name_map)
+    }
+
+    fn paper_snarl_map() -> (SnarlMap, NameMap) {
+        use crate::cactusgraph::{
+            build_snarl_family, BridgeForest, CactusGraph, CactusTree,
+        };
+
+        let (graph, name_map) = paper_graph();
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+
+        let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+        (snarl_map, name_map)
+    }
+
+    // Only (7, 22) contains a black bridge edge in the paper graph, so
+    // its snarl tree is flat. Marking the black edges of the a and m..p
+    // segments as contained in the snarls next to them gives the tree
+    // tests some nesting to work with.
+    fn nested_paper_snarl_map() -> SnarlMap {
+        let (mut snarl_map, _) = paper_snarl_map();
+
+        let marks = [
+            (1, 6, 0),
+            (24, 31, 30),
+            (25, 30, 24),
+            (26, 29, 28),
+            (27, 28, 26),
+            (27, 29, 26),
+            (27, 29, 28),
+        ];
+
+        for &(x, y, bridge) in marks.iter() {
+            snarl_map
+                .mark_snarl(Node::new(x), Node::new(y), Node::new(bridge), true)
+                .unwrap();
+        }
+
+        snarl_map
+    }
+
+    #[test]
+    fn paper_snarl_depths() {
+        let snarl_map = nested_paper_snarl_map();
+        let snarl_count = snarl_map.snarls.len();
+
+        let snarl_tree = SnarlTree::from_snarl_map(snarl_map);
+
+        let histogram = snarl_tree.depth_histogram();
+
+        assert_eq!(histogram.values().sum::<usize>(), snarl_count);
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            vec![(0, 13), (1, 2)]
+        );
+
+        let nested = snarl_tree
+            .map
+            .snarls
+            .keys()
+            .filter(|&&ix| snarl_tree.depth(ix) == Some(1))
+            .count();
+        assert_eq!(nested, 2);
+
+        assert_eq!(snarl_tree.depth(snarl_count + 1), None);
+    }
+
+    #[test]
+    fn paper_snarl_lca() {
+        let snarl_map = nested_paper_snarl_map();
+        let snarl_tree = SnarlTree::from_snarl_map(snarl_map);
+        let ix = |x: u64, y: u64| {
+            snarl_tree
+                .map
+                .get_snarl_ix(Node::new(x), Node::new(y))
+                .unwrap()
+        };
+
+        // (27, 29) is the only snarl containing others, (26, 29) and
+        // (27, 28)
+        let outer = ix(27, 29);
+
+        assert_eq!(snarl_tree.lca(ix(26, 29), ix(27, 28)), Some(outer));
+        assert_eq!(snarl_tree.lca(ix(26, 29), outer), Some(outer));
+        assert_eq!(snarl_tree.lca(ix(27, 28), ix(27, 28)), Some(ix(27, 28)));
+
+        assert_eq!(snarl_tree.lca(ix(26, 29), ix(2, 4)), None);
+        assert_eq!(snarl_tree.lca(outer, 1000), None);
+    }
+
+    #[test]
+    fn deeply_nested_snarl_tree() {
+        let mut map = SnarlMap::default();
+
+        let ranks = (0..10)
+            .map(|i| {
+                let snarl = Snarl::chain_pair(Node::new(i), Node::new(100 - i));
+                map.insert(snarl);
+                map.get_snarl_ix(snarl.left(), snarl.right()).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        // every snarl contains all the snarls inside it
+        let tree = ranks
+            .iter()
+            .enumerate()
+            .map(|(i, &ix)| (ix, ranks[i + 1..].iter().copied().collect()))
+            .collect();
+
+        let snarl_tree = SnarlTree { map, tree };
+
+        assert_eq!(snarl_tree.max_depth(), 9);
+        assert_eq!(snarl_tree.depth(ranks[9]), Some(9));
+
+        assert_eq!(snarl_tree.assert_depth_below(10), Ok(()));
+        assert_eq!(snarl_tree.assert_depth_below(9), Err(9));
+        assert_eq!(snarl_tree.assert_depth_below(3), Err(9));
+
+        let paper_tree = SnarlTree::from_snarl_map(nested_paper_snarl_map());
+        assert_eq!(paper_tree.max_depth(), 1);
+
+        let (snarl_map, _) = paper_snarl_map();
+        let paper_tree = SnarlTree::from_snarl_map(snarl_map);
+        assert_eq!(paper_tree.max_depth(), 0);
+    }
+
+    #[test]
+    fn snarl_tree_traversals() {
+        let mut map = SnarlMap::default();
+
+        // a contains b and c, b contains d and e, and c contains f
+        let ranks = [(0, 50), (1, 20), (21, 40), (2, 10), (11, 19), (22, 30)]
+            .iter()
+            .map(|&(x, y)| {
+                let snarl = Snarl::chain_pair(Node::new(x), Node::new(y));
+                map.insert(snarl);
+                map.get_snarl_ix(snarl.left(), snarl.right()).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let (a, b, c, d, e, f) =
+            (ranks[0], ranks[1], ranks[2], ranks[3], ranks[4], ranks[5]);
+
+        // like the trees built from snarl maps, each snarl contains
+        // all the snarls nested anywhere inside it
+        let tree = vec![
+            (a, vec![b, c, d, e, f].into_iter().collect()),
+            (b, vec![d, e].into_iter().collect()),
+            (c, vec![f].into_iter().collect()),
+        ]
+        .into_iter()
+        .collect();
+
+        let snarl_tree = SnarlTree { map, tree };
+
+        assert_eq!(
+            snarl_tree.dfs(a).collect::<Vec<_>>(),
+            vec![a, b, d, e, c, f]
+        );
+        assert_eq!(
+            snarl_tree.bfs(a).collect::<Vec<_>>(),
+            vec![a, b, c, d, e, f]
+        );
+
+        assert_eq!(snarl_tree.dfs(b).collect::<Vec<_>>(), vec![b, d, e]);
+        assert_eq!(snarl_tree.bfs(f).collect::<Vec<_>>(), vec![f]);
+        assert_eq!(snarl_tree.dfs(ranks.len()).count(), 0);
+
+        let paper_tree = SnarlTree::from_snarl_map(nested_paper_snarl_map());
+
+        let ix = |x: u64, y: u64| {
+            paper_tree
+                .map
+                .get_snarl_ix(Node::new(x), Node::new(y))
+                .unwrap()
+        };
+
+        let mut children = [ix(26, 29), ix(27, 28)];
+        children.sort_unstable();
+
+        // the subtree at (27, 29) is only one level deep, so both
+        // orders are the same
+        let root = ix(27, 29);
+        let dfs = paper_tree.dfs(root).collect::<Vec<_>>();
+        assert_eq!(dfs, vec![root, children[0], children[1]]);
+        assert_eq!(paper_tree.bfs(root).collect::<Vec<_>>(), dfs);
+    }
+
+    #[test]
+    fn paper_nested_snarls() {
+        let snarl_map = nested_paper_snarl_map();
+        let snarl_tree = SnarlTree::from_snarl_map(snarl_map);
+
+        let ix = |x: u64, y: u64| {
+            snarl_tree
+                .map
+                .get_snarl_ix(Node::new(x), Node::new(y))
+                .unwrap()
+        };
+
+        let outer = ix(27, 29);
+
+        assert!(snarl_tree.contains_snarl(outer, ix(26, 29)));
+        assert!(snarl_tree.contains_snarl(outer, ix(27, 28)));
+
+        assert!(!snarl_tree.contains_snarl(ix(26, 29), outer));
+        assert!(!snarl_tree.contains_snarl(outer, outer));
+        assert!(!snarl_tree.contains_snarl(outer, ix(2, 4)));
+        assert!(!snarl_tree.contains_snarl(ix(26, 29), ix(27, 28)));
+    }
+
+    #[test]
+    fn paper_bubble_traversals() {
+        let (graph, _) = paper_graph();
+        let (snarl_map, _) = paper_snarl_map();
+
+        let nodes = |ids: &[u64]| -> Vec<Node> {
+            ids.iter().map(|&n| Node::new(n)).collect()
+        };
+
+        // segment 7 is followed either by segment 8 and then 9, or
+        // by 9 directly
+        let (x, y) = (Node::new(15), Node::new(18));
+
+        let paths = snarl_traversals(&snarl_map, &graph, x, y, 10);
+        assert_eq!(paths, vec![nodes(&[15, 16, 17, 18]), nodes(&[15, 18])]);
+
+        let paths = snarl_traversals(&snarl_map, &graph, x, y, 1);
+        assert_eq!(paths.len(), 1);
+
+        let missing =
+            snarl_traversals(&snarl_map, &graph, x, Node::new(19), 10);
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn paper_snarl_complexity() {
+        let (graph, _) = paper_graph();
+        let (snarl_map, _) = paper_snarl_map();
+
+        let complexity = |x: u64, y: u64| {
+            snarl_map.complexity(Node::new(x), Node::new(y), &graph)
+        };
+
+        // segment 8 is either included or skipped
+        assert_eq!(complexity(15, 18), Some(SnarlComplexity::Simple));
+
+        // the bridge pair between segments 3 and 11 branches at 3, 5,
+        // 6, and 7
+        assert_eq!(complexity(7, 22), Some(SnarlComplexity::Complex));
+
+        assert_eq!(complexity(15, 19), None);
+    }
+
+    #[test]
+    fn shared_snarl_map_queries() {
+        use std::sync::Arc;
+
+        fn assert_sync<T: Send + Sync>() {}
+        assert_sync::<SharedSnarlMap>();
+
+        let (snarl_map, _) = paper_snarl_map();
+        let expected = snarl_map.to_vec();
+
+        // the snarls containing each black edge, as found by querying
+        // the map itself
+        let containing = (0..18)
+            .map(|segment| {
+                let (left, _) = Node::from_gfa_id(segment);
+                expected
+                    .iter()
+                    .copied()
+                    .filter(|s| {
+                        snarl_map
+                            .snarl_contains(s.left(), s.right())
+                            .is_some_and(|c| c.contains(left))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let shared = Arc::new(snarl_map.freeze());
+        assert_eq!(shared.len(), expected.len());
+
+        let handles = (0..4)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                let expected = expected.clone();
+                let containing = containing.clone();
+                std::thread::spawn(move || {
+                    for snarl in expected {
+                        let (x, y) = (snarl.left(), snarl.right());
+                        assert_eq!(shared.get(x, y), Some(snarl));
+                        assert!(shared.with_boundary(x).any(|s| s == snarl));
+                    }
+
+                    for (segment, snarls) in containing.iter().enumerate() {
+                        let (left, right) = Node::from_gfa_id(segment as u64);
+                        assert_eq!(shared.containing_snarls(left), &snarls[..]);
+                        assert_eq!(
+                            shared.containing_snarls(right),
+                            &snarls[..]
+                        );
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn three_allele_bubble() {
+        use crate::cactusgraph::{
+            build_snarl_family, BridgeForest, CactusGraph, CactusTree,
+        };
+
+        // segment 1 is followed by one of 2, 3, and 4, and then 5
+        let gfa = "S\t1\t*\nS\t2\t*\nS\t3\t*\nS\t4\t*\nS\t5\t*\n\
+                   L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\n\
+                   L\t1\t+\t4\t+\t0M\nL\t2\t+\t5\t+\t0M\n\
+                   L\t3\t+\t5\t+\t0M\nL\t4\t+\t5\t+\t0M\n";
+
+        let graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_gfa_reader(gfa.as_bytes()).unwrap();
+
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+        let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+        // from the end of 1 to the start of 5
+        let (x, y) = (Node::new(3), Node::new(10));
+
+        assert_eq!(snarl_map.allele_count(x, y, &graph, 10), Some(3));
+        assert_eq!(snarl_map.allele_count(x, y, &graph, 2), Some(2));
+        assert_eq!(snarl_map.allele_count(x, Node::new(11), &graph, 10), None);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn paper_snarls_par_iter() {
+        let (snarl_map, _) = paper_snarl_map();
+
+        let tally = |mut counts: BTreeMap<SnarlType, usize>,
+                     snarl: &Snarl<()>| {
+            *counts.entry(snarl.snarl_type()).or_default() += 1;
+            counts
+        };
+
+        let serial = snarl_map.snarls.values().fold(BTreeMap::new(), tally);
+
+        let parallel = snarl_map
+            .par_iter()
+            .fold(BTreeMap::new, |counts, (_, snarl)| tally(counts, snarl))
+            .reduce(BTreeMap::new, |mut a, b| {
+                for (ty, n) in b {
+                    *a.entry(ty).or_default() += n;
+                }
+                a
+            });
+
+        assert_eq!(parallel, serial);
+        assert_eq!(parallel.values().sum::<usize>(), snarl_map.snarls.len());
+        assert_eq!(snarl_map.par_iter().count(), snarl_map.snarls.len());
+
+        let mut ranks =
+            snarl_map.par_iter().map(|(ix, _)| ix).collect::<Vec<_>>();
+        ranks.sort_unstable();
+        let mut expected = snarl_map.snarls.keys().copied().collect::<Vec<_>>();
+        expected.sort_unstable();
+        assert_eq!(ranks, expected);
+    }
+
+    #[test]
+    fn cyclic_snarl_interior() {
+        use crate::cactusgraph::{
+            build_snarl_family, BridgeForest, CactusGraph, CactusTree,
+        };
+
+        // segment 1 is followed either by 4 directly, or by 2 and 3,
+        // which can loop back to 2 any number of times before 4
+        let gfa = "S\t1\t*\nS\t2\t*\nS\t3\t*\nS\t4\t*\n\
+                   L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\n\
+                   L\t3\t+\t2\t+\t0M\nL\t3\t+\t4\t+\t0M\n\
+                   L\t1\t+\t4\t+\t0M\n";
+
+        let graph: BiedgedGraph<Biedged> =
+            BiedgedGraph::from_gfa_reader(gfa.as_bytes()).unwrap();
+
+        let cactus_graph = CactusGraph::from_biedged_graph(&graph);
+        let cactus_tree = CactusTree::from_cactus_graph(&cactus_graph);
+        let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
+        let snarl_map = build_snarl_family(&cactus_tree, &bridge_forest);
+
+        let is_acyclic = |x: u64, y: u64| {
+            snarl_map.is_acyclic(Node::new(x), Node::new(y), &graph)
+        };
+
+        assert_eq!(is_acyclic(3, 8), Some(false));
+        assert_eq!(is_acyclic(5, 6), Some(true));
+        assert_eq!(is_acyclic(3, 9), None);
+
+        let (graph, _) = paper_graph();
+        let (snarl_map, _) = paper_snarl_map();
+
+        // the snarls facing the right-hand end of the graph reach the
+        // cycle through segments m, n or o, and p
+        let cyclic = snarl_map
+            .to_vec()
+            .into_iter()
+            .filter(|s| {
+                snarl_map.is_acyclic(s.left(), s.right(), &graph) == Some(false)
+            })
+            .map(|s| (s.left().id, s.right().id))
+            .collect::<Vec<_>>();
+        assert_eq!(cyclic, vec![(3, 5), (9, 11), (13, 19)]);
+    }
+
+    #[test]
+    fn snarl_oriented_boundaries() {
+        use crate::ultrabubble::Ultrabubble;
+        use gfa::gfa::Orientation;
+
+        // 7+ to 9+, where the boundary with the lower ID is the
+        // right-hand side of segment 7
+        let snarl: Snarl<()> = Snarl::chain_pair(Node::new(18), Node::new(15));
+        assert_eq!(snarl.left(), Node::new(15));
+        assert_eq!(snarl, Snarl::chain_pair(Node::new(15), Node::new(18)));
+        assert_eq!(snarl.oriented_boundaries(), ((7, false), (9, false)));
+
+        // 13- to 14-
+        let snarl: Snarl<()> = Snarl::bridge_pair(Node::new(26), Node::new(29));
+        assert_eq!(snarl.oriented_boundaries(), ((13, true), (14, true)));
+
+        let ub = Ultrabubble::from_boundaries(
+            snarl.left(),
+            snarl.right(),
+            snarl.snarl_type(),
+        );
+        let ((start, _), (end, _)) = snarl.oriented_boundaries();
+        assert_eq!(ub.start, (start as usize, Orientation::Backward));
+        assert_eq!(ub.end, (end as usize, Orientation::Backward));
+    }
+
+    #[test]
+    fn paper_effective_snarl_types() {
+        let snarl_map = nested_paper_snarl_map();
+
+        let (x, y) = (Node::new(2), Node::new(4));
+        assert_eq!(
+            snarl_map.get(x, y).unwrap().snarl_type(),
+            SnarlType::ChainPair
+        );
+        assert_eq!(snarl_map.effective_type(x, y), Some(SnarlType::Trivial));
+
+        let (x, y) = (Node::new(7), Node::new(22));
+        assert_eq!(snarl_map.effective_type(x, y), Some(SnarlType::BridgePair));
+
+        assert_eq!(snarl_map.effective_type(Node::new(2), Node::new(5)), None);
+
+        assert_eq!(
+            snarl_map.type_counts().into_iter().collect::<Vec<_>>(),
+            vec![
+                (SnarlType::ChainPair, 5),
+                (SnarlType::BridgePair, 2),
+                (SnarlType::Trivial, 8)
+            ]
+        );
+    }
+
+    #[test]
+    fn collapse_trivial_snarl_runs() {
+        // both sides of segments 0 through 5, in order
+        let chain = (0..12).map(Node::new).collect::<Vec<_>>();
+
+        let mut snarl_map = SnarlMap::default();
+        for &(x, y) in [(1, 2), (3, 4), (5, 6), (7, 8), (9, 10)].iter() {
+            snarl_map.insert(Snarl::chain_pair(Node::new(x), Node::new(y)));
+        }
+
+        // segment 4 is a bridge in the snarl between 7 and 8, which
+        // splits the runs
+        snarl_map.mark_snarl(Node::new(7), Node::new(8), Node::new(8), true);
+
+        snarl_map.collapse_trivial_chains(&chain);
+
+        let mut snarls = snarl_map
+            .snarls
+            .values()
+            .map(|s| (s.left().id, s.right().id))
+            .collect::<Vec<_>>();
+        snarls.sort_unstable();
+        assert_eq!(snarls, vec![(1, 6), (7, 8), (9, 10)]);
+
+        let contained = snarl_map
+            .snarl_contains(Node::new(1), Node::new(6))
+            .unwrap();
+        assert_eq!(contained.len(), 2);
+        assert!(contained.contains(Node::new(2)));
+        assert!(contained.contains(Node::new(5)));
+        assert_eq!(
+            snarl_map.is_trivial(Node::new(1), Node::new(6)),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn paper_overlapping_snarls() {
+        let (snarl_map, _) = paper_snarl_map();
+
+        let overlapping = |a: u64, b: u64| {
+            snarl_map
+                .overlapping(Node::new(a), Node::new(b))
+                .into_iter()
+                .map(|s| (s.left().id, s.right().id))
+                .collect::<Vec<_>>()
+        };
+
+        // the end of the bridge pair from segment 3 to 11, and the
+        // start of the snarl following it at segment 12
+        assert_eq!(overlapping(20, 24), vec![(7, 22), (24, 31)]);
+        assert_eq!(overlapping(24, 20), overlapping(20, 24));
+
+        assert_eq!(overlapping(22, 22), vec![(7, 22)]);
+        assert!(overlapping(32, 35).is_empty());
+    }
+
+    #[test]
+    fn paper_snarls_to_vec() {
+        let (snarl_map, _) = paper_snarl_map();
+
+        let snarls = snarl_map.to_vec();
+        assert_eq!(snarls.len(), snarl_map.snarls.len());
+        assert!(snarls.windows(2).all(|w| w[0] < w[1]));
+
+        let mut reversed = SnarlMap::default();
+        for &snarl in snarls.iter().rev() {
+            reversed.insert(snarl);
+        }
+
+        assert_ne!(
+            reversed.get_snarl_ix(snarls[0].left(), snarls[0].right()),
+            snarl_map.get_snarl_ix(snarls[0].left(), snarls[0].right())
+        );
+        assert_eq!(reversed.to_vec(), snarls);
+    }
+
+    #[test]
+    fn paper_snarl_reference_coords() {
+        let (mut snarl_map, name_map) = paper_snarl_map();
+
+        // a, b, d, e, g, l, m, n, p, q, with segment `a` having length
+        // 10, `b` 20, and so on
+        let path = b"abdeglmnpq"
+            .iter()
+            .map(|&name| {
+                let id = name_map.map_name(&[name][..]).unwrap();
+                (id as u64, false)
+            })
+            .collect::<Vec<_>>();
+        let lengths: FxHashMap<u64, usize> =
+            (0..18).map(|i| (i, 10 * (i as usize + 1))).collect();
+
+        snarl_map.annotate_reference(&path, &lengths);
+
+        let coords = |snarl_map: &SnarlMap, x: u64, y: u64| {
+            let ix = snarl_map.get_snarl_ix(Node::new(x), Node::new(y))?;
+            snarl_map.reference_coords(ix)
+        };
+
+        // from the end of a to the start of d
+        assert_eq!(coords(&snarl_map, 1, 6), Some((10, 30)));
+        // from the end of d to the start of l
+        assert_eq!(coords(&snarl_map, 7, 22), Some((70, 190)));
+        // from the start of m to the end of p
+        assert_eq!(coords(&snarl_map, 24, 31), Some((310, 740)));
+        assert_eq!(coords(&snarl_map, 25, 30), Some((440, 580)));
+        // c and o aren't on the path
+        assert_eq!(coords(&snarl_map, 3, 5), None);
+        assert_eq!(coords(&snarl_map, 26, 28), None);
+
+        let annotated = snarl_map.ref_coords.len();
+        let removed = snarl_map.remove(Node::new(1), Node::new(6));
+        assert!(removed.is_some());
+        assert_eq!(snarl_map.ref_coords.len(), annotated - 1);
+
+        snarl_map.compact();
+        assert_eq!(coords(&snarl_map, 7, 22), Some((70, 190)));
+    }
+
+    #[test]
+    fn paper_snarl_of_link() {
+        let (snarl_map, name_map) = paper_snarl_map();
+
+        let seg = |name: &str| name_map.map_name(name).unwrap() as u64;
+
+        let boundaries = |from: (u64, bool), to: (u64, bool)| {
+            let snarl = snarl_map.snarl_of_link(from, to)?;
+            Some((snarl.left().id, snarl.right().id))
+        };
+
+        // h+ -> i+ starts at the right-hand side of h, a boundary of
+        // (15, 18)
+        let h_i = boundaries((seg("h"), false), (seg("i"), false));
+        assert_eq!(h_i, Some((15, 18)));
+
+        // the same gray edge, from the other strand
+        let i_h = boundaries((seg("i"), true), (seg("h"), true));
+        assert_eq!(i_h, h_i);
+
+        // d+ -> e+ is inside (7, 22), which contains the black edge of
+        // d, but also inside (8, 10), which contains nothing
+        let d_e = boundaries((seg("d"), false), (seg("e"), false));
+        assert_eq!(d_e, Some((8, 10)));
+
+        // e+ -> g+ joins a boundary of (9, 11) to one of (12, 14), and
+        // neither contains anything, so the first of them is picked
+        let e_g = boundaries((seg("e"), false), (seg("g"), false));
+        assert_eq!(e_g, Some((9, 11)));
+
+        // l+ -> m+ enters the chain pair on the left-hand side of m
+        let l_m = boundaries((seg("l"), false), (seg("m"), false));
+        assert_eq!(l_m, Some((24, 31)));
+
+        // no snarl is anywhere near segments that aren't in the graph
+        assert_eq!(boundaries((100, false), (101, false)), None);
+    }
+
+    #[test]
+    fn extend_matches_repeated_inserts() {
+        let mut rng = 0x2545_f491_4f6c_dd1du64;
+        let mut next = || {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            rng % 200
+        };
+
+        // with only 200 vertices, many of the snarls are duplicates,
+        // possibly with a different type
+        let snarls = (0..10_000)
+            .map(|i| {
+                let (x, y) = (Node::new(next()), Node::new(next()));
+                if i & 1 == 0 {
+                    Snarl::chain_pair(x, y)
+                } else {
+                    Snarl::bridge_pair(x, y)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut inserted = SnarlMap::default();
+        inserted.insert(snarls[0]);
+        for &snarl in snarls.iter() {
+            inserted.insert(snarl);
+        }
+
+        let mut extended = SnarlMap::default();
+        extended.insert(snarls[0]);
+        extended.extend(snarls.iter().copied());
+
+        assert!(extended.snarls.len() < snarls.len());
+        assert_eq!(extended.snarls, inserted.snarls);
+        assert_eq!(extended.lefts, inserted.lefts);
+        assert_eq!(extended.rights, inserted.rights);
+    }
+
+    #[test]
+    fn paper_snarl_net_graph() {
+        let (graph, _) = paper_graph();
+        let (snarl_map, _) = paper_snarl_map();
+
+        let net =
+            snarl_map.snarl_net_graph(Node::new(7), Node::new(22), &graph);
+
+        // the boundaries of the chain pairs nested in (7, 22) are all
+        // in its net graph
+        for &(x, y) in [(8, 10), (9, 11), (12, 14), (15, 18)].iter() {
+            assert!(snarl_map.get(Node::new(x), Node::new(y)).is_some());
+            assert!(net.contains_node(Node::new(x)));
+            assert!(net.contains_node(Node::new(y)));
+        }
+
+        // the interior of (15, 18), segment 8, is replaced by a black
+        // edge between its boundaries
+        assert_eq!(
+            net.edge_weight(Node::new(15), Node::new(18)),
+            Some(BiedgedWeight::black(1))
+        );
+        assert!(!net.contains_node(Node::new(16)));
+        assert!(!net.contains_node(Node::new(17)));
+        assert_eq!(net.node_count(), 14);
+
+        // (1, 6) has no children, so its net graph is its interior
+        let net = snarl_map.snarl_net_graph(Node::new(1), Node::new(6), &graph);
+        let mut edges = net
+            .graph
+            .all_edges()
+            .map(|(a, b, _)| (a.min(b), a.max(b)))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        assert_eq!(
+            edges,
+            snarl_map.interior_edges(Node::new(1), Node::new(6), &graph)
+        );
+
+        let missing =
+            snarl_map.snarl_net_graph(Node::new(1), Node::new(7), &graph);
+        assert_eq!(missing.node_count(), 0);
+    }
+
+    #[test]
+    fn paper_snarl_map_diff() {
+        let (a, _) = paper_snarl_map();
+        let mut b = a.clone();
+
+        assert!(diff_snarl_maps(&a, &b).is_empty());
+
+        let dropped = b.remove(Node::new(1), Node::new(6)).unwrap();
+        b.mark_snarl(Node::new(7), Node::new(22), Node::new(8), true);
+        let changed = b.get(Node::new(7), Node::new(22)).unwrap();
+
+        // renumbering the ranks doesn't count as a difference
+        b.compact();
+
+        let diff = diff_snarl_maps(&a, &b);
+        assert_eq!(diff.only_in_a, vec![dropped]);
+        assert!(diff.only_in_b.is_empty());
+        assert_eq!(diff.containment_differs, vec![changed]);
+
+        let reverse = diff_snarl_maps(&b, &a);
+        assert!(reverse.only_in_a.is_empty());
+        assert_eq!(reverse.only_in_b, vec![dropped]);
+        assert_eq!(reverse.containment_differs, vec![changed]);
+    }
+
+    #[test]
+    fn paper_snarl_original_segments() {
+        let (graph, _) = paper_graph();
+        let (snarl_map, _) = paper_snarl_map();
+
+        let segments = |x: u64, y: u64| {
+            let ix = snarl_map.get_snarl_ix(Node::new(x), Node::new(y));
+            snarl_map.original_segments(ix.unwrap(), &graph)
+        };
+
+        // the bridge pair between segments 3 and 11 contains everything
+        // between them, but not the segments themselves
+        assert_eq!(segments(7, 22), vec![4, 5, 6, 7, 8, 9, 10]);
+
+        // segment 12 is followed by 13 or 14, and then 15
+        assert_eq!(segments(25, 30), vec![13, 14]);
+
+        assert_eq!(segments(1, 6), vec![1, 2]);
+        assert_eq!(segments(15, 18), vec![8]);
+
+        assert!(snarl_map.original_segments(1000, &graph).is_empty());
+    }
+
+    #[test]
+    fn paper_snarls_in_chain_order() {
+        let (snarl_map, _) = paper_snarl_map();
+
+        let nodes = |ids: &[u64]| -> Vec<Node> {
+            ids.iter().map(|&n| Node::new(n)).collect()
+        };
+
+        // the path through segments 0, 1, 3, 6, 11, 12, 13, 15, 16,
+        // both ends of each in order
+        let chain = [0, 1, 3, 6, 11, 12, 13, 15, 16]
+            .iter()
+            .flat_map(|&s| {
+                let (left, right) = Node::from_gfa_id(s);
+                vec![left, right]
+            })
+            .collect::<Vec<_>>();
+
+        let ordered = |chain: &[Node]| {
+            snarl_map
+                .snarls_in_chain_order(chain)
+                .into_iter()
+                .map(|s| (s.left().id, s.right().id))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ordered(&chain),
+            vec![
+                (1, 6),
+                (2, 4),
+                (3, 5),
+                (7, 22),
+                (12, 14),
+                (13, 19),
+                (24, 31),
+                (25, 30),
+                (26, 28),
+                (26, 29),
+                (27, 28),
+                (27, 29)
+            ]
+        );
+
+        // the ends of segments 15 and 13, in the opposite order
+        let chain = nodes(&[31, 30, 27, 26]);
+        assert_eq!(
+            ordered(&chain),
+            vec![(27, 28), (27, 29), (26, 28), (26, 29)]
+        );
+    }
+
+    #[test]
+    fn audit_paper_snarls() {
+        let (graph, _) = paper_graph();
+        let (mut snarl_map, _) = paper_snarl_map();
+
+        assert!(snarl_map.audit(&graph).is_empty());
+
+        let ix = snarl_map.get_snarl_ix(Node::new(2), Node::new(4)).unwrap();
+
+        // Point one of the snarl's boundaries outside the graph, and
+        // mark an edge that doesn't exist as contained
+        let snarl = snarl_map.snarls.get_mut(&ix).unwrap();
+        snarl.right = Node::new(101);
+        snarl_map
+            .snarl_contains
+            .entry(ix)
+            .or_default()
+            .insert(Node::new(200));
+
+        assert_eq!(
+            snarl_map.audit(&graph),
+            vec![
+                SnarlAuditError::DanglingBoundary {
+                    ix,
+                    node: Node::new(101)
+                },
+                SnarlAuditError::MissingContainedEdge {
+                    ix,
+                    edge: Node::new(200)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn paper_snarls_bed() {
+        let (snarl_map, name_map) = paper_snarl_map();
+
+        let mut out: Vec<u8> = Vec::new();
+        write_snarls_bed(&snarl_map, &name_map, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        // the last column counts the black bridge edges contained in
+        // each snarl, which the paper graph only marks for d_/l
+        let expected = [
+            "a_\td\tbridge\t0",
+            "b\tc\tchain\t0",
+            "b_\tc_\tchain\t0",
+            "d_\tl\tbridge\t1",
+            "e\tf\tchain\t0",
+            "e_\tf_\tchain\t0",
+            "g\th\tchain\t0",
+            "g_\tj_\tchain\t0",
+            "h_\tj\tchain\t0",
+            "m\tp_\tchain\t0",
+            "m_\tp\tchain\t0",
+            "n\to\tchain\t0",
+            "n\to_\tchain\t0",
+            "n_\to\tchain\t0",
+            "n_\to_\tchain\t0",
+        ];
+
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+        assert!(out.ends_with('\n'));
+    }
+
+    #[test]
+    fn paper_snarl_regions() {
+        let (mut snarl_map, name_map) = paper_snarl_map();
+
+        let path = b"abdeglmnpq"
+            .iter()
+            .map(|&name| {
+                let id = name_map.map_name(&[name][..]).unwrap();
+                (id as u64, false)
+            })
+            .collect::<Vec<_>>();
+        let lengths: FxHashMap<u64, usize> =
+            (0..18).map(|i| (i, 10 * (i as usize + 1))).collect();
+
+        snarl_map.annotate_reference(&path, &lengths);
+
+        let mut out: Vec<u8> = Vec::new();
+        write_snarl_regions(&snarl_map, &name_map, "ref", &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let expected = [
+            "#CHROM\tPOS\tEND\tID",
+            "ref\t11\t30\ta_-d",
+            "ref\t71\t190\td_-l",
+            "ref\t311\t740\tm-p_",
+            "ref\t441\t580\tm_-p",
+        ];
+
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+
+        // only the header, without a path
+        snarl_map.annotate_reference(&[], &lengths);
+        let mut out: Vec<u8> = Vec::new();
+        write_snarl_regions(&snarl_map, &name_map, "ref", &mut out).unwrap();
+        assert_eq!(out, b"#CHROM\tPOS\tEND\tID\n");
+    }
+
+    #[test]
+    fn snarl_boundary_candidates() {
+        let (graph, _) = paper_graph();
+
+        let valid = [(1, 6), (7, 22), (24, 31), (26, 29), (0, 35), (0, 1)];
+        for &(x, y) in valid.iter() {
+            let (x, y) = (Node::new(x), Node::new(y));
+            assert!(is_valid_boundary(&graph, x, y), "{:?}", (x, y));
+            assert!(is_valid_boundary(&graph, y, x), "{:?}", (y, x));
+        }
+
+        let invalid = [(6, 6), (1, 36), (36, 37), (100, 1)];
+        for &(x, y) in invalid.iter() {
+            let (x, y) = (Node::new(x), Node::new(y));
+            assert!(!is_valid_boundary(&graph, x, y), "{:?}", (x, y));
+        }
+
+        // vertices 4 and 5 only have gray edges, and segment 3 is
+        // disconnected from the rest
+        let graph: BiedgedGraph<Biedged> = BiedgedGraph::from_edges(
+            &[(0, 1), (2, 3), (6, 7)],
+            &[(1, 2), (3, 4), (4, 5)],
+        );
+
+        assert!(is_valid_boundary(&graph, Node::new(0), Node::new(3)));
+        assert!(!is_valid_boundary(&graph, Node::new(0), Node::new(4)));
+        assert!(!is_valid_boundary(&graph, Node::new(5), Node::new(3)));
+        assert!(!is_valid_boundary(&graph, Node::new(0), Node::new(6)));
+        assert!(is_valid_boundary(&graph, Node::new(6), Node::new(7)));
+    }
+
+    #[test]
+    fn paper_node_roles() {
+        let (snarl_map, _) = paper_snarl_map();
+
+        let role = |n: u64| snarl_map.node_role(Node::new(n));
+
+        for &n in [1, 6, 7, 22].iter() {
+            assert_eq!(role(n), NodeRole::BridgeBoundary);
+        }
+
+        for &n in [2, 5, 13, 19, 26, 29, 31].iter() {
+            assert_eq!(role(n), NodeRole::ChainBoundary);
+        }
+
+        // none of these are the boundary of a snarl
+        for &n in [0, 35, 16, 17, 20, 21].iter() {
+            assert_eq!(role(n), NodeRole::Interior);
+        }
+
+        // being the boundary of a chain pair takes precedence
+        let mut snarl_map = SnarlMap::default();
+        snarl_map.insert(Snarl::chain_pair(Node::new(2), Node::new(4)));
+        snarl_map.insert(Snarl::bridge_pair(Node::new(4), Node::new(9)));
+
+        assert_eq!(snarl_map.node_role(Node::new(4)), NodeRole::ChainBoundary);
+        assert_eq!(snarl_map.node_role(Node::new(9)), NodeRole::BridgeBoundary);
+        assert_eq!(snarl_map.node_role(Node::new(3)), NodeRole::Interior);
+    }
 }
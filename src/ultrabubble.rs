@@ -1,3 +1,7 @@
+use gfa::gfa::Orientation;
+
+use crate::snarls::{Node, SnarlType};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ChainPair {
     pub x: u64,
@@ -15,3 +19,40 @@ pub struct ChainEdge {
     pub net: u64,
     pub chain: u64,
 }
+
+/// An ultrabubble in the original graph, given by the segments at its
+/// two boundaries. Each boundary is oriented as it's traversed when
+/// walking through the ultrabubble from `start` to `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ultrabubble {
+    pub start: (usize, Orientation),
+    pub end: (usize, Orientation),
+    pub ty: SnarlType,
+}
+
+impl Ultrabubble {
+    /// Construct an ultrabubble from its boundary vertices in the
+    /// biedged graph. The vertex with the lower ID is used as the
+    /// start.
+    pub fn from_boundaries(x: Node, y: Node, ty: SnarlType) -> Self {
+        let (x, y) = if x <= y { (x, y) } else { (y, x) };
+
+        let start_orient = if x.is_right() {
+            Orientation::Forward
+        } else {
+            Orientation::Backward
+        };
+
+        let end_orient = if y.is_left() {
+            Orientation::Forward
+        } else {
+            Orientation::Backward
+        };
+
+        Self {
+            start: (x.to_gfa_id() as usize, start_orient),
+            end: (y.to_gfa_id() as usize, end_orient),
+            ty,
+        }
+    }
+}